@@ -7,263 +7,347 @@ use bevy::{
 };
 use bevy_spatial::kdtree::KDTree2;
 use bevy_spatial::SpatialAccess;
+use cosmos_raiders_sim::{
+    bombs::{bottom_of_columns, choose_shooter, drop_position, BombClock, Shot},
+    march::{self, formation_velocity},
+    simulation::laser_hits_alien,
+};
 
-use crate::game::ships::Laser;
+use crate::game::ships::{Laser, PlayerShip};
 
 use super::{
-    collisions::{collide, CollisionMatrices},
+    bombs::{ForAnyBomb, PlungerBomb, SquigglyBomb},
+    collisions::CollisionMatrices,
     explosions::Explosion,
-    AssetHandles, AtlasIndexable, Spawnable,
+    formations::ActiveFormation,
+    kinds::{AlienKind, AlienKindId, AlienKinds},
+    waves::Wave,
+    AssetHandles, InGameMarker, Spawnable,
 };
 
-#[derive(Component, Default)]
-pub struct Alien<const POINT_VALUE: u32, const SPRITE_INDEX: usize>;
-
-#[derive(Copy, Clone, Resource, PartialEq, Default)]
-pub enum AlienMovement {
-    Left,
-    #[default]
-    Right,
-    Down {
-        pixels_left_to_move: f32,
-        should_move_left_after: bool,
-    },
+/// An alien of any kind. Everything that differs between kinds of alien lives
+/// in the [`AlienKinds`] registry, so new kinds can be added without any code
+/// changes.
+#[derive(Component)]
+pub struct Alien {
+    pub kind: AlienKindId,
+    hitpoints: u32,
+    shield: u32,
+    /// The index into the kind's sprite frames that is currently showing.
+    frame: usize,
+}
+
+impl Alien {
+    fn new(kind_id: AlienKindId, kind: &AlienKind) -> Self {
+        Self {
+            kind: kind_id,
+            hitpoints: kind.hitpoints,
+            shield: kind.shield,
+            frame: 0,
+        }
+    }
+
+    fn color(&self) -> Color {
+        if self.shield > 0 {
+            SHIELDED_COLOR
+        } else {
+            Color::WHITE
+        }
+    }
 }
 
+/// Aliens are tinted this colour while their shield is up.
+const SHIELDED_COLOR: Color = Color::rgb(0.5, 0.7, 1.0);
+/// How far apart the aliens a splitting alien splits into are spawned.
+const SPLIT_SPACING_X: f32 = 16.0;
+
+#[derive(Copy, Clone, Resource, PartialEq, Default, Deref, DerefMut)]
+/// A global resource storing where the formation is in its march.
+pub struct AlienMovement(march::AlienMovement);
+
 #[derive(Copy, Clone, Resource, PartialEq, Deref, DerefMut)]
 /// A global resource storing the current velocity of all aliens.
 pub struct AlienVelocity(pub f32);
 
 impl Default for AlienVelocity {
     fn default() -> Self {
-        Self(100.)
+        Self(BASE_VELOCITY)
     }
 }
 
-// Type aliases for different levels of aliens. Allows one implementation of
-// Alien for different aliens with different point values and sprite indices
-// statically.
-/// Low level aliens are worth 10 points and have a sprite index of 4.
-pub type LowLevelAlien = Alien<10, 4>;
-/// Mid level aliens are worth 20 points and have a sprite index of 2.
-pub type MidLevelAlien = Alien<20, 2>;
-/// High level aliens are worth 30 points and have a sprite index of 0.
-pub type HighLevelAlien = Alien<30, 0>;
-
-/// A type alias for a Bevy filter that matches any entity with a low, mid, or
-/// high level alien component.
-pub type ForAnyAlien = Or<(
-    With<LowLevelAlien>,
-    With<MidLevelAlien>,
-    With<HighLevelAlien>,
-)>;
+/// The velocity of the aliens before any formation has been spawned, in pixels
+/// per second.
+const BASE_VELOCITY: f32 = 100.0;
 
-#[derive(Component)]
-/// A component that stores the original sprite index of an alien. The
-/// alternator system uses this to determine which sprite to switch to.
-/// TODO: this can be optimised in iteration 3 - this is definitely not the most
-/// efficient way to do this.
-pub struct CurrentSpriteIndex {
-    original: usize,
-    current: usize,
+#[derive(Resource, Default)]
+/// A global resource tracking the formation's march, which drives the
+/// four-note heartbeat and the alien sprite animation.
+pub struct MarchBeat {
+    steps: march::MarchSteps,
+    /// The index of the next note to play.
+    note: usize,
 }
 
-impl<const P: u32, const I: usize> Spawnable for Alien<P, I> {
-    fn spawn(pos: Vec3, texture_atlas: Handle<TextureAtlas>, commands: &mut Commands) {
-        commands.spawn((
-            Alien::<P, I>::default(),
-            CurrentSpriteIndex {
-                original: I,
-                current: I,
-            },
-            SpriteSheetBundle {
-                texture_atlas,
-                transform: Transform::from_translation(pos),
-                sprite: TextureAtlasSprite::new(I),
-                ..Default::default()
-            },
-        ));
+/// Sent every time the formation takes a step of the march.
+#[derive(Event)]
+pub struct MarchStepEvent;
+
+#[derive(Resource, Default, Deref, DerefMut)]
+/// A global resource controlling how often the aliens drop bombs.
+pub struct AlienFiring(BombClock);
+
+impl AlienFiring {
+    /// Returns the firing cadence for the given wave. The aliens drop bombs
+    /// more often on every wave.
+    pub fn for_wave(wave: u32) -> Self {
+        Self(BombClock::for_wave(wave))
     }
 }
 
-impl<const P: u32, const I: usize> Alien<P, I> {
-    const POINT_VALUE: u32 = P;
-
-    pub fn laser_collision_sys(
-        lasers: Query<(Entity, &Transform), With<Laser>>,
-        mut commands: Commands,
-        mut score: ResMut<Score>,
-        asset_handles: Res<AssetHandles>,
-        alien_spatial_tree: Res<KDTree2<Self>>,
-        alien_sprite_indices: Query<&CurrentSpriteIndex, With<Self>>,
-        matrices: Res<CollisionMatrices>,
-        mut rumble_requests: EventWriter<GamepadRumbleRequest>,
-        gamepads: Res<Gamepads>,
-    ) {
-        for (laser_entity, laser_transform) in lasers.iter() {
-            let laser_pos = laser_transform.translation.truncate();
-
-            if let Some((alien_pos, entity)) = alien_spatial_tree.nearest_neighbour(laser_pos) {
-                let alien_entity = match entity {
-                    Some(entity) => entity,
-                    None => return,
-                };
-
-                let alien_sprite_index = match alien_sprite_indices.get(alien_entity) {
-                    Ok(sprite_index) => sprite_index,
-                    Err(_) => return,
-                };
-
-                if collide(
-                    &matrices,
-                    Laser::SPRITE_INDEX,
-                    alien_sprite_index.current,
-                    laser_pos,
-                    alien_pos,
-                ) {
-                    commands.entity(alien_entity).despawn();
-                    commands.entity(laser_entity).despawn();
-                    score.0 += Self::POINT_VALUE;
-                    commands.spawn(AudioBundle {
-                        source: asset_handles.explosion_sound.clone(),
-                        ..Default::default()
-                    });
-                    Explosion::spawn(
-                        alien_pos.extend(0.),
-                        asset_handles.texture_atlas.clone(),
-                        &mut commands,
-                    );
-                    for gamepad in gamepads.iter() {
-                        rumble_requests.send(GamepadRumbleRequest::Add {
-                            gamepad,
-                            intensity: GamepadRumbleIntensity::STRONG_MAX,
-                            duration: Duration::from_millis(1000),
-                        });
-                    }
-                }
-            }
-        }
-    }
+/// A procedure that spawns a single alien of the given kind.
+pub fn spawn_alien(
+    commands: &mut Commands,
+    texture_atlas: Handle<TextureAtlas>,
+    kinds: &AlienKinds,
+    kind_id: AlienKindId,
+    pos: Vec3,
+) {
+    let Some(kind) = kinds.get(&kind_id) else {
+        warn!("No alien kind is called {kind_id:?}, skipping it");
+        return;
+    };
+    let alien = Alien::new(kind_id, kind);
+    let mut sprite = TextureAtlasSprite::new(kind.frames[0]);
+    sprite.color = alien.color();
+    commands.spawn((
+        alien,
+        InGameMarker,
+        SpriteSheetBundle {
+            texture_atlas,
+            transform: Transform::from_translation(pos),
+            sprite,
+            ..Default::default()
+        },
+    ));
 }
-const SCREEN_BOUNDARY_X: f32 = 300.0;
-
-/// A procedure that spawns all the aliens in the game.
-pub fn spawn_aliens(commands: &mut Commands, texture_atlas_handle: &Handle<TextureAtlas>) {
-    for alien_row in 0..5 {
-        let y = 200.0 - (alien_row as f32 * 32.0);
-        for alien_col in 0..11 {
-            let x = -300.0 + (alien_col as f32 * 32.0);
-            match alien_row {
-                0 => HighLevelAlien::spawn(
-                    Vec3::new(x, y, 0.0),
-                    texture_atlas_handle.clone(),
-                    commands,
-                ),
-                1 | 2 => MidLevelAlien::spawn(
-                    Vec3::new(x, y, 0.0),
-                    texture_atlas_handle.clone(),
-                    commands,
-                ),
-                3 | 4 => LowLevelAlien::spawn(
-                    Vec3::new(x, y, 0.0),
-                    texture_atlas_handle.clone(),
-                    commands,
-                ),
-                _ => unreachable!(),
+
+/// A system that damages aliens hit by the player's laser. Hits are absorbed by
+/// the alien's shield first, then its hitpoints. Aliens that run out of
+/// hitpoints are killed, and split into smaller aliens if their kind does.
+pub fn laser_collision_sys(
+    lasers: Query<(Entity, &Transform), With<Laser>>,
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    asset_handles: Res<AssetHandles>,
+    alien_spatial_tree: Res<KDTree2<Alien>>,
+    mut aliens: Query<(&mut Alien, &mut TextureAtlasSprite)>,
+    kinds: Res<AlienKinds>,
+    matrices: Res<CollisionMatrices>,
+    mut rumble_requests: EventWriter<GamepadRumbleRequest>,
+    gamepads: Res<Gamepads>,
+) {
+    for (laser_entity, laser_transform) in lasers.iter() {
+        let laser_pos = laser_transform.translation.truncate();
+
+        let Some((alien_pos, Some(alien_entity))) = alien_spatial_tree.nearest_neighbour(laser_pos)
+        else {
+            continue;
+        };
+
+        let Ok((mut alien, mut sprite)) = aliens.get_mut(alien_entity) else {
+            continue;
+        };
+        // the alien may have been killed by another laser this frame
+        if alien.hitpoints == 0 {
+            continue;
+        }
+
+        if !laser_hits_alien(&matrices, laser_pos, sprite.index, alien_pos) {
+            continue;
+        }
+
+        commands.entity(laser_entity).despawn();
+
+        if alien.shield > 0 {
+            alien.shield -= 1;
+            sprite.color = alien.color();
+            continue;
+        }
+        alien.hitpoints -= 1;
+        if alien.hitpoints > 0 {
+            continue;
+        }
+
+        // the alien's kind may have been removed from the kinds file since it
+        // spawned, in which case it's worth nothing
+        let kind = kinds.get(&alien.kind);
+        commands.entity(alien_entity).despawn();
+        score.0 += kind.map_or(0, |kind| kind.points);
+        commands.spawn(AudioBundle {
+            source: asset_handles.explosion_sound.clone(),
+            settings: PlaybackSettings::DESPAWN,
+        });
+        Explosion::spawn(
+            alien_pos.extend(0.),
+            asset_handles.texture_atlas.clone(),
+            &mut commands,
+        );
+        if let Some(split) = kind.and_then(|kind| kind.splits_into.as_ref()) {
+            let first_x = alien_pos.x - (split.count as f32 - 1.0) * SPLIT_SPACING_X / 2.0;
+            for i in 0..split.count {
+                let x = first_x + i as f32 * SPLIT_SPACING_X;
+                spawn_alien(
+                    &mut commands,
+                    asset_handles.texture_atlas.clone(),
+                    &kinds,
+                    split.kind.clone(),
+                    Vec3::new(x, alien_pos.y, 0.0),
+                );
             }
         }
+        for gamepad in gamepads.iter() {
+            rumble_requests.send(GamepadRumbleRequest::Add {
+                gamepad,
+                intensity: GamepadRumbleIntensity::STRONG_MAX,
+                duration: Duration::from_millis(1000),
+            });
+        }
     }
 }
 
-/// How many logical pixels to move the aliens down by when they hit the screen
-/// boundary.
-const DOWN_STEP_Y: f32 = 24.0;
-
 /// A system responsible for moving aliens.
 pub fn movement_sys(
     time: Res<Time>,
-    mut query: Query<&mut Transform, ForAnyAlien>,
+    mut query: Query<&mut Transform, With<Alien>>,
     mut movement: ResMut<AlienMovement>,
     velocity: Res<AlienVelocity>,
 ) {
-    let dt = time.delta_seconds();
-    let pixels_moved_this_frame = **velocity * dt;
+    let xs = query.iter().map(|transform| transform.translation.x);
+    let delta = movement.step(xs, **velocity * time.delta_seconds());
+    for mut transform in query.iter_mut() {
+        transform.translation += delta.extend(0.0);
+    }
+}
 
-    match *movement {
-        AlienMovement::Left => {
-            for mut transform in query.iter_mut() {
-                transform.translation.x -= pixels_moved_this_frame;
-            }
-            let left_most_position = query
-                .iter()
-                .map(|t| t.translation.x)
-                .fold(f32::INFINITY, f32::min);
-            if left_most_position <= -SCREEN_BOUNDARY_X {
-                *movement = AlienMovement::Down {
-                    pixels_left_to_move: DOWN_STEP_Y,
-                    should_move_left_after: false,
-                };
-            }
-        }
-        AlienMovement::Right => {
-            for mut transform in query.iter_mut() {
-                transform.translation.x += pixels_moved_this_frame;
-            }
-            let right_most_position = query
-                .iter()
-                .map(|t| t.translation.x)
-                .fold(f32::NEG_INFINITY, f32::max);
-            if right_most_position >= SCREEN_BOUNDARY_X {
-                *movement = AlienMovement::Down {
-                    pixels_left_to_move: DOWN_STEP_Y,
-                    should_move_left_after: true,
-                };
-            }
-        }
-        AlienMovement::Down {
-            ref mut pixels_left_to_move,
-            should_move_left_after,
-        } => {
-            for mut transform in query.iter_mut() {
-                // Move the alien down
-                let move_down = f32::min(*pixels_left_to_move, pixels_moved_this_frame);
-                transform.translation.y -= move_down;
-            }
-            *pixels_left_to_move -= pixels_moved_this_frame;
-
-            // If the aliens have finished moving down, change horizontal direction
-            if *pixels_left_to_move <= 0.0 {
-                *movement = if should_move_left_after {
-                    AlienMovement::Left
-                } else {
-                    AlienMovement::Right
-                };
-            }
-        }
+/// A system that moves on to the next wave once every alien is dead. Each wave
+/// starts lower, marches faster and drops bombs more often than the last.
+pub fn respawn_sys(aliens: Query<(), With<Alien>>, mut wave: ResMut<Wave>) {
+    if wave.formation_spawned && aliens.iter().len() == 0 {
+        wave.advance();
     }
 }
 
-/// A system that respawns aliens if there are none left.
-pub fn respawn_sys(
-    aliens: Query<(), ForAnyAlien>,
+/// Returns the position of the bottom-most alien in each column of the
+/// formation. Only these aliens have a clear line of fire to drop bombs, and
+/// only if their kind drops bombs at all.
+fn bottom_aliens<'a>(
+    aliens: impl Iterator<Item = (&'a Transform, &'a Alien)>,
+    kinds: &AlienKinds,
+) -> Vec<Vec2> {
+    let aliens = aliens.map(|(transform, alien)| (transform.translation.truncate(), &alien.kind));
+    bottom_of_columns(aliens)
+        .into_iter()
+        .filter(|(_, kind)| kinds.get(kind).is_some_and(|kind| kind.drops_bombs))
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+/// A system that makes the aliens drop bombs. Shots alternate between a
+/// squiggly bomb from the column closest to the player and a plunger bomb from
+/// a random column.
+pub fn firing_sys(
+    time: Res<Time>,
+    mut firing: ResMut<AlienFiring>,
+    aliens: Query<(&Transform, &Alien)>,
+    kinds: Res<AlienKinds>,
+    ships: Query<&Transform, With<PlayerShip>>,
+    bombs: Query<(), ForAnyBomb>,
+    asset_handles: Res<AssetHandles>,
     mut commands: Commands,
+) {
+    let Some(shot) = firing.tick(time.delta_seconds(), bombs.iter().count()) else {
+        return;
+    };
+
+    let shooters = bottom_aliens(aliens.iter(), &kinds);
+    let ship_x = ships.get_single().ok().map(|ship| ship.translation.x);
+    let Some(shooter) = choose_shooter(shot, &shooters, ship_x, &mut rand::thread_rng()) else {
+        return;
+    };
+    let pos = drop_position(shooter).extend(0.0);
+    let atlas = asset_handles.texture_atlas.clone();
+    match shot {
+        Shot::Aimed => SquigglyBomb::spawn(pos, atlas, &mut commands),
+        Shot::Random => PlungerBomb::spawn(pos, atlas, &mut commands),
+    }
+}
+
+/// A system that speeds the formation up as aliens are killed.
+pub fn speed_up_sys(
+    aliens: Query<(), With<Alien>>,
+    wave: Res<Wave>,
+    formation: Res<ActiveFormation>,
+    mut velocity: ResMut<AlienVelocity>,
+) {
+    let remaining = aliens.iter().len();
+    if remaining == 0 {
+        return;
+    }
+    let new_velocity = formation_velocity(
+        remaining,
+        formation.size,
+        formation.velocity * wave.velocity_multiplier(),
+    );
+    // avoid triggering change detection every frame
+    if **velocity != new_velocity {
+        **velocity = new_velocity;
+    }
+}
+
+/// A system that plays the four-note marching heartbeat. The beat is tied to
+/// the distance the formation has marched, so its tempo follows
+/// [`AlienVelocity`].
+pub fn march_sys(
+    time: Res<Time>,
+    velocity: Res<AlienVelocity>,
+    mut beat: ResMut<MarchBeat>,
+    aliens: Query<(), With<Alien>>,
     asset_handles: Res<AssetHandles>,
+    mut commands: Commands,
+    mut steps: EventWriter<MarchStepEvent>,
 ) {
-    if aliens.iter().len() == 0 {
-        spawn_aliens(&mut commands, &asset_handles.texture_atlas)
+    if aliens.is_empty() {
+        return;
+    }
+
+    if !beat.steps.advance(**velocity * time.delta_seconds()) {
+        return;
     }
+
+    commands.spawn(AudioBundle {
+        source: asset_handles.march_sounds[beat.note].clone(),
+        settings: PlaybackSettings::DESPAWN,
+    });
+    beat.note = (beat.note + 1) % asset_handles.march_sounds.len();
+    steps.send(MarchStepEvent);
 }
 
-/// A system that alternates the sprite of aliens.
+/// A system that steps every alien on to its kind's next sprite on every step
+/// of the march.
 pub fn sprite_alternator_sys(
-    mut aliens: Query<(&mut CurrentSpriteIndex, &mut TextureAtlasSprite), ForAnyAlien>,
+    mut steps: EventReader<MarchStepEvent>,
+    mut aliens: Query<(&mut Alien, &mut TextureAtlasSprite)>,
+    kinds: Res<AlienKinds>,
 ) {
-    for (mut csi, mut sprite) in aliens.iter_mut() {
-        if csi.current == csi.original {
-            csi.current = csi.original + 1;
-        } else {
-            csi.current = csi.original;
-        }
-        sprite.index = csi.current;
+    if steps.iter().count() == 0 {
+        return;
+    }
+    for (mut alien, mut sprite) in aliens.iter_mut() {
+        let Some(kind) = kinds.get(&alien.kind) else {
+            continue;
+        };
+        let frames = &kind.frames;
+        alien.frame = (alien.frame + 1) % frames.len();
+        sprite.index = frames[alien.frame];
     }
 }
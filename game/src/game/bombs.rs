@@ -0,0 +1,85 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use cosmos_raiders_sim::simulation::{bomb_hits_ship, BOMB_VELOCITY};
+
+use super::{
+    collisions::CollisionMatrices,
+    ships::{Invulnerable, PlayerShip},
+    AtlasIndexable,
+};
+
+/// A bomb dropped by an alien. Different bombs only differ in how they look,
+/// so the sprite index is used to tell them apart statically.
+#[derive(Component, Default)]
+pub struct Bomb<const SPRITE_INDEX: usize>;
+
+impl<const I: usize> AtlasIndexable for Bomb<I> {
+    const SPRITE_INDEX: usize = I;
+}
+
+/// Plunger bombs have a sprite index of 9 and are dropped from a random column.
+pub type PlungerBomb = Bomb<9>;
+/// Squiggly bombs have a sprite index of 11 and are dropped from the column
+/// closest to the player.
+pub type SquigglyBomb = Bomb<11>;
+
+/// A type alias for a Bevy filter that matches any entity with a bomb
+/// component.
+pub type ForAnyBomb = Or<(With<PlungerBomb>, With<SquigglyBomb>)>;
+
+/// Sent when a bomb hits the player's ship.
+#[derive(Event)]
+pub struct ShipHitEvent {
+    pub position: Vec2,
+}
+
+impl<const I: usize> Bomb<I> {
+    /// Despawn the bomb if it goes off the bottom of the screen
+    fn needs_despawn(pos: &Vec3, window_height: f32) -> bool {
+        pos.y < -window_height / 2.0
+    }
+
+    pub fn movement_sys(
+        time: Res<Time>,
+        mut bombs: Query<(Entity, &mut Transform), With<Self>>,
+        mut commands: Commands,
+        windows: Query<&Window, With<PrimaryWindow>>,
+    ) {
+        let Ok(window) = windows.get_single() else {
+            return;
+        };
+        let dt = time.delta_seconds();
+        for (entity, mut trans) in bombs.iter_mut() {
+            trans.translation.y -= BOMB_VELOCITY * dt;
+            if Self::needs_despawn(&trans.translation, window.height()) {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+
+    pub fn ship_collision_sys(
+        bombs: Query<(Entity, &Transform), With<Self>>,
+        ships: Query<&Transform, (With<PlayerShip>, Without<Invulnerable>)>,
+        matrices: Res<CollisionMatrices>,
+        mut commands: Commands,
+        mut ship_hits: EventWriter<ShipHitEvent>,
+    ) {
+        let Ok(ship_transform) = ships.get_single() else {
+            return;
+        };
+        let ship_pos = ship_transform.translation.truncate();
+
+        for (bomb_entity, bomb_transform) in bombs.iter() {
+            if bomb_hits_ship(
+                &matrices,
+                Self::SPRITE_INDEX,
+                bomb_transform.translation.truncate(),
+                ship_pos,
+            ) {
+                commands.entity(bomb_entity).despawn();
+                ship_hits.send(ShipHitEvent { position: ship_pos });
+                // only one bomb needs to hit the ship
+                return;
+            }
+        }
+    }
+}
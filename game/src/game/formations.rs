@@ -0,0 +1,396 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::{BoxedFuture, HashSet},
+};
+use cosmos_raiders_sim::march::SCREEN_BOUNDARY_X;
+use serde::Deserialize;
+
+use super::{
+    aliens::{spawn_alien, Alien, AlienFiring, AlienMovement},
+    kinds::{self, AlienKinds, ALIEN_KINDS_PATH},
+    waves::Wave,
+    AssetHandles,
+};
+
+/// The file every wave's formation is loaded from, relative to the assets
+/// folder.
+pub const FORMATIONS_PATH: &str = "waves.formations.ron";
+
+/// A single wave's formation, as written in a formations file.
+#[derive(Debug, Deserialize)]
+struct FormationFile {
+    /// One string per row of the formation, top row first. Each character is a
+    /// cell: the symbol of a kind of alien from the alien kinds file, or `.`
+    /// for no alien.
+    grid: Vec<String>,
+    /// The horizontal and vertical distance between neighbouring cells.
+    spacing: (f32, f32),
+    /// The y position of the top row.
+    start_y: f32,
+    /// The velocity of the full formation, in pixels per second.
+    velocity: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormationsFile {
+    waves: Vec<FormationFile>,
+}
+
+#[derive(Debug)]
+pub enum FormationError {
+    NoWaves,
+    /// The formation for a wave, counting from 1, can't be used.
+    BadWave {
+        wave: usize,
+        problem: WaveProblem,
+    },
+}
+
+impl std::fmt::Display for FormationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormationError::NoWaves => write!(f, "the formations file has no waves"),
+            FormationError::BadWave { wave, problem } => {
+                write!(f, "the formation for wave {wave} {problem}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormationError {}
+
+/// What's wrong with a single wave's formation.
+#[derive(Debug, PartialEq)]
+pub enum WaveProblem {
+    NoAliens,
+    /// Rows count from 1. Rows with no aliens in them are written with dots, so
+    /// an empty string is most likely a mistake.
+    EmptyRow {
+        row: usize,
+    },
+    BadSpacing,
+    BadVelocity,
+    UnknownKind {
+        symbol: char,
+    },
+}
+
+impl std::fmt::Display for WaveProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaveProblem::NoAliens => write!(f, "has no aliens"),
+            WaveProblem::EmptyRow { row } => {
+                write!(
+                    f,
+                    "has nothing in row {row}, use dots for a row with no aliens"
+                )
+            }
+            WaveProblem::BadSpacing => write!(f, "must have a positive spacing"),
+            WaveProblem::BadVelocity => write!(f, "must have a positive velocity"),
+            WaveProblem::UnknownKind { symbol } => {
+                write!(f, "uses the symbol '{symbol}', which no alien kind has")
+            }
+        }
+    }
+}
+
+fn is_positive(x: f32) -> bool {
+    x > 0.0 && x.is_finite()
+}
+
+/// A single wave's formation, ready to be spawned.
+#[derive(Debug)]
+pub struct Formation {
+    /// The position of every alien relative to the top left cell, along with
+    /// the symbol of its kind. Symbols are checked against the alien kinds file
+    /// when the formations are loaded, but only looked up when the formation is
+    /// spawned, so the alien kinds can still be edited independently.
+    cells: Vec<(Vec2, char)>,
+    start_y: f32,
+    velocity: f32,
+}
+
+impl TryFrom<FormationFile> for Formation {
+    type Error = WaveProblem;
+
+    fn try_from(file: FormationFile) -> Result<Self, Self::Error> {
+        let (spacing_x, spacing_y) = file.spacing;
+        if !is_positive(spacing_x) || !is_positive(spacing_y) {
+            return Err(WaveProblem::BadSpacing);
+        }
+        if !is_positive(file.velocity) {
+            return Err(WaveProblem::BadVelocity);
+        }
+        let mut cells = Vec::new();
+        for (row, line) in file.grid.iter().enumerate() {
+            if line.is_empty() {
+                return Err(WaveProblem::EmptyRow { row: row + 1 });
+            }
+            for (col, symbol) in line.chars().enumerate() {
+                // `.` and spaces are empty cells
+                if symbol == '.' || symbol == ' ' {
+                    continue;
+                }
+                let offset = Vec2::new(col as f32 * spacing_x, -(row as f32) * spacing_y);
+                cells.push((offset, symbol));
+            }
+        }
+        if cells.is_empty() {
+            return Err(WaveProblem::NoAliens);
+        }
+        Ok(Self {
+            cells,
+            start_y: file.start_y,
+            velocity: file.velocity,
+        })
+    }
+}
+
+impl Formation {
+    /// Checks that every cell's symbol belongs to a kind of alien.
+    fn check_kinds(&self, symbols: &HashSet<char>) -> Result<(), WaveProblem> {
+        match self
+            .cells
+            .iter()
+            .find(|(_, symbol)| !symbols.contains(symbol))
+        {
+            Some(&(_, symbol)) => Err(WaveProblem::UnknownKind { symbol }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Every wave's formation. Once the last one has been cleared the waves repeat
+/// from the start, still getting harder each time.
+#[derive(Debug, TypeUuid, TypePath)]
+#[uuid = "6f0c4f0e-3b8e-4c39-9f5c-2d8f7a0e1b54"]
+pub struct Formations {
+    waves: Vec<Formation>,
+}
+
+impl Formations {
+    pub fn for_wave(&self, wave: u32) -> &Formation {
+        &self.waves[(wave as usize - 1) % self.waves.len()]
+    }
+}
+
+#[derive(Default)]
+pub struct FormationsLoader;
+
+impl AssetLoader for FormationsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let file: FormationsFile = ron::de::from_bytes(bytes)?;
+            if file.waves.is_empty() {
+                return Err(FormationError::NoWaves.into());
+            }
+            // a kind removed from the kinds file later is only noticed when
+            // its aliens are spawned, see `spawn_formation`
+            let symbols = kinds::symbols(&load_context.read_asset_bytes(ALIEN_KINDS_PATH).await?)?;
+            let waves = file
+                .waves
+                .into_iter()
+                .enumerate()
+                .map(|(i, wave)| {
+                    Formation::try_from(wave)
+                        .and_then(|formation| {
+                            formation.check_kinds(&symbols)?;
+                            Ok(formation)
+                        })
+                        .map_err(|problem| FormationError::BadWave {
+                            wave: i + 1,
+                            problem,
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            load_context.set_default_asset(LoadedAsset::new(Formations { waves }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["formations.ron"]
+    }
+}
+
+#[derive(Resource, Default)]
+/// A global resource describing the formation currently in play.
+pub struct ActiveFormation {
+    /// How many aliens the formation started with.
+    pub size: usize,
+    /// The velocity of the full formation, before any wave speed-up.
+    pub velocity: f32,
+}
+
+/// A procedure that spawns a formation of aliens. Its top row is moved down by
+/// `drop_y`. Returns the number of aliens spawned.
+fn spawn_formation(
+    commands: &mut Commands,
+    texture_atlas_handle: &Handle<TextureAtlas>,
+    kinds: &AlienKinds,
+    formation: &Formation,
+    drop_y: f32,
+) -> usize {
+    // the left-most column starts at the edge of the screen
+    let origin = Vec2::new(-SCREEN_BOUNDARY_X, formation.start_y - drop_y);
+    let mut spawned = 0;
+    for &(offset, symbol) in &formation.cells {
+        let Some(kind) = kinds.by_symbol(symbol) else {
+            warn!("No alien kind uses the symbol '{symbol}', skipping it");
+            continue;
+        };
+        let pos = (origin + offset).extend(0.0);
+        spawn_alien(commands, texture_atlas_handle.clone(), kinds, kind, pos);
+        spawned += 1;
+    }
+    spawned
+}
+
+/// A system that spawns the current wave's formation once the formations and
+/// alien kinds files have loaded.
+pub fn spawn_formation_sys(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    mut active: ResMut<ActiveFormation>,
+    mut movement: ResMut<AlienMovement>,
+    asset_handles: Res<AssetHandles>,
+    formations: Res<Assets<Formations>>,
+    kinds: Res<AlienKinds>,
+) {
+    if wave.formation_spawned || kinds.is_empty() {
+        return;
+    }
+    let Some(formations) = formations.get(&asset_handles.formations) else {
+        return;
+    };
+
+    let formation = formations.for_wave(wave.number);
+    let size = spawn_formation(
+        &mut commands,
+        &asset_handles.texture_atlas,
+        &kinds,
+        formation,
+        wave.drop_y(),
+    );
+    *active = ActiveFormation {
+        size,
+        velocity: formation.velocity,
+    };
+    *movement = AlienMovement::default();
+    commands.insert_resource(AlienFiring::for_wave(wave.number));
+    wave.formation_spawned = true;
+}
+
+/// A system that rebuilds the current wave when the formations or alien kinds
+/// files are edited while the game is running.
+pub fn hot_reload_sys(
+    mut formation_events: EventReader<AssetEvent<Formations>>,
+    mut kind_events: EventReader<AssetEvent<AlienKinds>>,
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    aliens: Query<Entity, With<Alien>>,
+) {
+    // read both readers every frame, so old events aren't seen later
+    let formations_modified = formation_events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+    let kinds_modified = kind_events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+    if !(formations_modified || kinds_modified) || !wave.formation_spawned {
+        return;
+    }
+
+    info!("Wave assets changed, rebuilding wave {}", wave.number);
+    for alien in &aliens {
+        commands.entity(alien).despawn();
+    }
+    wave.formation_spawned = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formation(ron: &str) -> Result<Formation, WaveProblem> {
+        Formation::try_from(ron::from_str::<FormationFile>(ron).unwrap())
+    }
+
+    #[test]
+    fn the_shipped_formations_use_the_shipped_kinds() {
+        let file: FormationsFile =
+            ron::from_str(include_str!("../../assets/waves.formations.ron")).unwrap();
+        let symbols = kinds::symbols(include_bytes!("../../assets/aliens.kinds.ron")).unwrap();
+        assert!(!file.waves.is_empty());
+        for wave in file.waves {
+            Formation::try_from(wave)
+                .unwrap()
+                .check_kinds(&symbols)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn cells_are_spaced_from_the_top_left() {
+        let formation = formation(
+            r#"(grid: ["H.H", " L "], spacing: (30.0, 20.0), start_y: 200.0, velocity: 100.0)"#,
+        )
+        .unwrap();
+        assert_eq!(
+            formation.cells,
+            vec![
+                (Vec2::new(0.0, 0.0), 'H'),
+                (Vec2::new(60.0, 0.0), 'H'),
+                (Vec2::new(30.0, -20.0), 'L'),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_formations_are_rejected() {
+        let cases = [
+            (
+                r#"["...", "..."], spacing: (32.0, 32.0), velocity: 100.0"#,
+                WaveProblem::NoAliens,
+            ),
+            (
+                r#"["HHH", "", "LLL"], spacing: (32.0, 32.0), velocity: 100.0"#,
+                WaveProblem::EmptyRow { row: 2 },
+            ),
+            (
+                r#"["HHH"], spacing: (0.0, 32.0), velocity: 100.0"#,
+                WaveProblem::BadSpacing,
+            ),
+            (
+                r#"["HHH"], spacing: (32.0, -1.0), velocity: 100.0"#,
+                WaveProblem::BadSpacing,
+            ),
+            (
+                r#"["HHH"], spacing: (32.0, 32.0), velocity: 0.0"#,
+                WaveProblem::BadVelocity,
+            ),
+        ];
+        for (fields, problem) in cases {
+            let ron = format!("(grid: {fields}, start_y: 200.0)");
+            assert_eq!(formation(&ron).unwrap_err(), problem, "{ron}");
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_rejected() {
+        let formation =
+            formation(r#"(grid: ["HXH"], spacing: (32.0, 32.0), start_y: 200.0, velocity: 100.0)"#)
+                .unwrap();
+        let symbols = HashSet::from_iter(['H', 'L']);
+        assert_eq!(
+            formation.check_kinds(&symbols),
+            Err(WaveProblem::UnknownKind { symbol: 'X' })
+        );
+    }
+}
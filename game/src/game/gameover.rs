@@ -1,21 +1,44 @@
-use crate::game::aliens::ForAnyAlien;
-use crate::game::ships::PlayerShip;
-use bevy::app::AppExit;
+use crate::game::aliens::Alien;
+use crate::game::ships::{PlayerShip, ShipWreck};
+use crate::GameState;
 use bevy::prelude::*;
 
-use super::scoreboard::Score;
+use super::{highscores::HighScores, lives::Lives, scoreboard::Score};
+
+/// Ends the run, going to the initials entry screen first if the score made it
+/// onto the high score table.
+fn end_run(score: &Score, high_scores: &HighScores, next_state: &mut NextState<GameState>) {
+    info!("Game Over - score {}", score.0);
+    if high_scores.qualifies(score.0) {
+        next_state.set(GameState::HighScoreEntry);
+    } else {
+        next_state.set(GameState::GameOver);
+    }
+}
 
 pub fn game_over_sys(
-    aliens: Query<&Transform, ForAnyAlien>,
+    aliens: Query<&Transform, With<Alien>>,
     ships: Query<&Transform, With<PlayerShip>>,
+    wrecks: Query<(), With<ShipWreck>>,
+    lives: Res<Lives>,
     score: Res<Score>,
-    mut exit: EventWriter<AppExit>,
+    high_scores: Res<HighScores>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    let ship_pos = ships.get_single().unwrap().translation;
-    for alien_pos in aliens.iter() {
-        if alien_pos.translation.y < ship_pos.y {
-            println!("Game Over - score {}", score.0);
-            exit.send(AppExit);
-        }
+    // the last ship has been destroyed and its death animation has finished
+    if lives.remaining == 0 && ships.is_empty() && wrecks.is_empty() {
+        end_run(&score, &high_scores, &mut next_state);
+        return;
+    }
+
+    let Ok(ship_transform) = ships.get_single() else {
+        return;
+    };
+    let ship_pos = ship_transform.translation;
+    if aliens
+        .iter()
+        .any(|alien_pos| alien_pos.translation.y < ship_pos.y)
+    {
+        end_run(&score, &high_scores, &mut next_state);
     }
 }
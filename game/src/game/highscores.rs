@@ -0,0 +1,206 @@
+use std::{fs, io, path::PathBuf};
+
+use bevy::prelude::*;
+use chrono::{Local, NaiveDate};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    scoreboard::{
+        Score, SCOREBOARD_FONT_SIZE, SCOREBOARD_PADDING_PX, SCOREBOARD_TEXT_PADDING, TEXT_COLOR,
+    },
+    InGameMarker,
+};
+
+/// The name of the high score file, inside the platform's data directory.
+const HIGH_SCORES_FILE: &str = "highscores.ron";
+/// The version of the high score file format written by this build. Bump this
+/// and handle the old version in [`HighScores::load`] whenever the format
+/// changes.
+const HIGH_SCORES_VERSION: u32 = 1;
+/// How many entries the high score table keeps.
+pub const MAX_ENTRIES: usize = 10;
+/// How many letters of initials each entry has, like the arcade original.
+pub const INITIALS_LEN: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub initials: String,
+    pub score: u32,
+    pub wave: u32,
+    pub date: NaiveDate,
+}
+
+impl HighScoreEntry {
+    /// Returns an entry for a run that has just finished.
+    pub fn new(initials: String, score: u32, wave: u32) -> Self {
+        Self {
+            initials,
+            score,
+            wave,
+            date: Local::now().date_naive(),
+        }
+    }
+}
+
+/// The high score file, as written to disk.
+#[derive(Debug, Serialize, Deserialize)]
+struct HighScoresFile {
+    version: u32,
+    entries: Vec<HighScoreEntry>,
+}
+
+#[derive(Debug)]
+pub enum HighScoreError {
+    NoDataDir,
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for HighScoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HighScoreError::NoDataDir => write!(f, "couldn't find the platform data directory"),
+            HighScoreError::Io(e) => write!(f, "{e}"),
+            HighScoreError::Parse(e) => write!(f, "couldn't parse the high score file: {e}"),
+            HighScoreError::Serialize(e) => write!(f, "couldn't write the high score file: {e}"),
+            HighScoreError::UnsupportedVersion(v) => {
+                write!(f, "the high score file has unsupported version {v}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HighScoreError {}
+
+impl From<io::Error> for HighScoreError {
+    fn from(e: io::Error) -> Self {
+        HighScoreError::Io(e)
+    }
+}
+
+/// Returns the path of the high score file.
+fn high_scores_path() -> Result<PathBuf, HighScoreError> {
+    let dirs = ProjectDirs::from("", "", "cosmos-raiders").ok_or(HighScoreError::NoDataDir)?;
+    Ok(dirs.data_dir().join(HIGH_SCORES_FILE))
+}
+
+#[derive(Resource, Debug, Default)]
+/// A global resource storing the best runs on this machine, best first.
+pub struct HighScores {
+    pub entries: Vec<HighScoreEntry>,
+}
+
+impl HighScores {
+    /// Loads the high score table from disk. A missing file is an empty table.
+    pub fn load() -> Result<Self, HighScoreError> {
+        let contents = match fs::read_to_string(high_scores_path()?) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let file: HighScoresFile = ron::from_str(&contents).map_err(HighScoreError::Parse)?;
+        if file.version != HIGH_SCORES_VERSION {
+            return Err(HighScoreError::UnsupportedVersion(file.version));
+        }
+        let mut scores = Self {
+            entries: file.entries,
+        };
+        scores.sort();
+        Ok(scores)
+    }
+
+    pub fn save(&self) -> Result<(), HighScoreError> {
+        let path = high_scores_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = HighScoresFile {
+            version: HIGH_SCORES_VERSION,
+            entries: self.entries.clone(),
+        };
+        let contents = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(HighScoreError::Serialize)?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// The best score on the table, or 0 if the table is empty.
+    pub fn best(&self) -> u32 {
+        self.entries.first().map_or(0, |entry| entry.score)
+    }
+
+    /// Whether a run with `score` points would make it onto the table.
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_ENTRIES
+                || self.entries.last().map_or(true, |last| score > last.score))
+    }
+
+    /// Adds an entry to the table, dropping the worst entry if it's full.
+    pub fn insert(&mut self, entry: HighScoreEntry) {
+        self.entries.push(entry);
+        self.sort();
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    fn sort(&mut self) {
+        // stable, so older entries stay ahead of newer ones with the same score
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score));
+    }
+}
+
+/// A procedure that loads the high score table into a resource, starting from
+/// an empty table if it can't be read.
+pub fn load_high_scores() -> HighScores {
+    HighScores::load().unwrap_or_else(|e| {
+        warn!("Couldn't load high scores, starting with an empty table: {e}");
+        HighScores::default()
+    })
+}
+
+/// marker component for the high score readout
+#[derive(Component, Default)]
+pub struct HighScoreDisplay;
+
+pub fn spawn_high_score_display(commands: &mut Commands, font: Handle<Font>) {
+    commands.spawn((
+        HighScoreDisplay,
+        InGameMarker,
+        TextBundle::from_sections([
+            TextSection::new(
+                "HI-SCORE: ",
+                TextStyle {
+                    font_size: SCOREBOARD_FONT_SIZE,
+                    font: font.clone(),
+                    color: TEXT_COLOR,
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font_size: SCOREBOARD_FONT_SIZE,
+                font,
+                color: TEXT_COLOR,
+            }),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            // just below the scoreboard
+            top: Val::Px(SCOREBOARD_PADDING_PX + SCOREBOARD_FONT_SIZE),
+            left: SCOREBOARD_TEXT_PADDING,
+            ..default()
+        }),
+    ));
+}
+
+/// A system that shows the best score, or the current score once the player
+/// has beaten it.
+pub fn update_sys(
+    high_scores: Res<HighScores>,
+    score: Res<Score>,
+    mut query: Query<&mut Text, With<HighScoreDisplay>>,
+) {
+    let mut text = query.single_mut();
+    text.sections[1].value = high_scores.best().max(score.0).to_string();
+}
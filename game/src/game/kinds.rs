@@ -0,0 +1,346 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::{BoxedFuture, HashMap, HashSet},
+};
+use serde::Deserialize;
+
+use super::AssetHandles;
+
+/// The file the alien kinds are loaded from, relative to the assets folder.
+pub const ALIEN_KINDS_PATH: &str = "aliens.kinds.ron";
+/// The number of sprites in the texture atlas.
+const ATLAS_SPRITES: usize = 16;
+
+fn default_hitpoints() -> u32 {
+    1
+}
+
+fn default_drops_bombs() -> bool {
+    true
+}
+
+/// What happens when a splitting alien is killed.
+#[derive(Debug, Clone, Deserialize)]
+struct SplitFile {
+    /// The name of the kind of alien to split into.
+    kind: String,
+    count: u32,
+}
+
+/// A single kind of alien, as written in an alien kinds file.
+#[derive(Debug, Deserialize)]
+struct AlienKindFile {
+    name: String,
+    /// The character that places this kind of alien in a formation grid.
+    symbol: char,
+    points: u32,
+    /// The sprites the alien steps through on each step of the march.
+    frames: Vec<usize>,
+    #[serde(default = "default_hitpoints")]
+    hitpoints: u32,
+    /// How many hits the alien's shield absorbs before the alien itself takes
+    /// damage.
+    #[serde(default)]
+    shield: u32,
+    #[serde(default = "default_drops_bombs")]
+    drops_bombs: bool,
+    #[serde(default)]
+    splits_into: Option<SplitFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlienKindsFile {
+    kinds: Vec<AlienKindFile>,
+}
+
+#[derive(Debug)]
+pub enum AlienKindError {
+    NoKinds,
+    DuplicateName(String),
+    DuplicateSymbol(char),
+    NoFrames { kind: String },
+    FrameOutOfRange { kind: String, frame: usize },
+    NoHitpoints { kind: String },
+    UnknownSplitKind { kind: String, splits_into: String },
+}
+
+impl std::fmt::Display for AlienKindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlienKindError::NoKinds => write!(f, "the alien kinds file has no kinds"),
+            AlienKindError::DuplicateName(name) => {
+                write!(f, "more than one alien kind is called {name}")
+            }
+            AlienKindError::DuplicateSymbol(c) => {
+                write!(f, "more than one alien kind uses the symbol '{c}'")
+            }
+            AlienKindError::NoFrames { kind } => write!(f, "alien kind {kind} has no frames"),
+            AlienKindError::FrameOutOfRange { kind, frame } => {
+                write!(
+                    f,
+                    "alien kind {kind} uses frame {frame}, which isn't in the atlas"
+                )
+            }
+            AlienKindError::NoHitpoints { kind } => {
+                write!(f, "alien kind {kind} must have at least 1 hitpoint")
+            }
+            AlienKindError::UnknownSplitKind { kind, splits_into } => {
+                write!(
+                    f,
+                    "alien kind {kind} splits into unknown kind {splits_into}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AlienKindError {}
+
+/// The name of a kind of alien, which is how aliens refer to their kind. Names
+/// survive the alien kinds file being edited and reloaded, so aliens keep their
+/// kind however the file is reordered, and find nothing if it was removed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AlienKindId(String);
+
+#[derive(Debug, Clone)]
+pub struct Split {
+    pub kind: AlienKindId,
+    pub count: u32,
+}
+
+/// A single kind of alien, ready to be spawned.
+#[derive(Debug, Clone)]
+pub struct AlienKind {
+    pub name: String,
+    pub points: u32,
+    pub frames: Vec<usize>,
+    pub hitpoints: u32,
+    pub shield: u32,
+    pub drops_bombs: bool,
+    pub splits_into: Option<Split>,
+}
+
+/// The registry of every kind of alien. It's loaded as an asset so it can be
+/// hot-reloaded, then copied into a resource of the same type by
+/// [`sync_sys`] so that systems can read it directly.
+#[derive(Debug, Clone, Default, Resource, TypeUuid, TypePath)]
+#[uuid = "0b5a8f3e-7d2c-4e61-a1f4-6c9e2b7d3a10"]
+pub struct AlienKinds {
+    kinds: HashMap<AlienKindId, AlienKind>,
+    by_symbol: HashMap<char, AlienKindId>,
+}
+
+impl AlienKinds {
+    /// Returns the kind with the given name, if the kinds file still has it.
+    pub fn get(&self, id: &AlienKindId) -> Option<&AlienKind> {
+        self.kinds.get(id)
+    }
+
+    /// Returns the kind of alien placed by `symbol` in a formation grid.
+    pub fn by_symbol(&self, symbol: char) -> Option<AlienKindId> {
+        self.by_symbol.get(&symbol).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+}
+
+impl TryFrom<AlienKindsFile> for AlienKinds {
+    type Error = AlienKindError;
+
+    fn try_from(file: AlienKindsFile) -> Result<Self, Self::Error> {
+        if file.kinds.is_empty() {
+            return Err(AlienKindError::NoKinds);
+        }
+
+        let mut by_symbol = HashMap::new();
+        let mut names = HashSet::new();
+        for kind in &file.kinds {
+            if !names.insert(kind.name.clone()) {
+                return Err(AlienKindError::DuplicateName(kind.name.clone()));
+            }
+            let id = AlienKindId(kind.name.clone());
+            if by_symbol.insert(kind.symbol, id).is_some() {
+                return Err(AlienKindError::DuplicateSymbol(kind.symbol));
+            }
+        }
+
+        let kinds = file
+            .kinds
+            .into_iter()
+            .map(|kind| {
+                if kind.frames.is_empty() {
+                    return Err(AlienKindError::NoFrames { kind: kind.name });
+                }
+                if let Some(&frame) = kind.frames.iter().find(|&&f| f >= ATLAS_SPRITES) {
+                    return Err(AlienKindError::FrameOutOfRange {
+                        kind: kind.name,
+                        frame,
+                    });
+                }
+                if kind.hitpoints == 0 {
+                    return Err(AlienKindError::NoHitpoints { kind: kind.name });
+                }
+                let splits_into = match kind.splits_into {
+                    Some(split) if names.contains(&split.kind) => Some(Split {
+                        kind: AlienKindId(split.kind),
+                        count: split.count,
+                    }),
+                    Some(split) => {
+                        return Err(AlienKindError::UnknownSplitKind {
+                            kind: kind.name,
+                            splits_into: split.kind,
+                        })
+                    }
+                    None => None,
+                };
+                let id = AlienKindId(kind.name.clone());
+                Ok((
+                    id,
+                    AlienKind {
+                        name: kind.name,
+                        points: kind.points,
+                        frames: kind.frames,
+                        hitpoints: kind.hitpoints,
+                        shield: kind.shield,
+                        drops_bombs: kind.drops_bombs,
+                        splits_into,
+                    },
+                ))
+            })
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        Ok(Self { kinds, by_symbol })
+    }
+}
+
+/// Returns the symbol of every kind of alien in an alien kinds file, so the
+/// formations can be checked against them as they're loaded.
+pub fn symbols(bytes: &[u8]) -> Result<HashSet<char>, bevy::asset::Error> {
+    let file: AlienKindsFile = ron::de::from_bytes(bytes)?;
+    let kinds = AlienKinds::try_from(file)?;
+    Ok(kinds.by_symbol.into_keys().collect())
+}
+
+#[derive(Default)]
+pub struct AlienKindsLoader;
+
+impl AssetLoader for AlienKindsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let file: AlienKindsFile = ron::de::from_bytes(bytes)?;
+            let kinds = AlienKinds::try_from(file)?;
+            load_context.set_default_asset(LoadedAsset::new(kinds));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["kinds.ron"]
+    }
+}
+
+/// A system that copies the alien kinds asset into the [`AlienKinds`] resource
+/// once it has loaded, and again whenever it's changed.
+pub fn sync_sys(
+    mut events: EventReader<AssetEvent<AlienKinds>>,
+    assets: Res<Assets<AlienKinds>>,
+    asset_handles: Res<AssetHandles>,
+    mut kinds: ResMut<AlienKinds>,
+) {
+    let modified = events.iter().any(|event| {
+        matches!(event, AssetEvent::Modified { handle } if *handle == asset_handles.alien_kinds)
+    });
+    if !modified && !kinds.is_empty() {
+        return;
+    }
+    if let Some(loaded) = assets.get(&asset_handles.alien_kinds) {
+        *kinds = loaded.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(ron: &str) -> Result<AlienKinds, AlienKindError> {
+        AlienKinds::try_from(ron::from_str::<AlienKindsFile>(ron).unwrap())
+    }
+
+    const TWO_KINDS: &str = r#"(kinds: [
+        (name: "high", symbol: 'H', points: 30, frames: [0, 1]),
+        (name: "low", symbol: 'L', points: 10, frames: [4, 5]),
+    ])"#;
+
+    #[test]
+    fn the_shipped_kinds_file_loads() {
+        let kinds = load(include_str!("../../assets/aliens.kinds.ron")).unwrap();
+        let splitter = kinds.get(&kinds.by_symbol('S').unwrap()).unwrap();
+        let split = splitter.splits_into.as_ref().unwrap();
+        assert_eq!(kinds.get(&split.kind).unwrap().name, "low");
+    }
+
+    #[test]
+    fn ids_survive_the_file_being_reordered_or_shrunk() {
+        let before = load(TWO_KINDS).unwrap();
+        let high = before.by_symbol('H').unwrap();
+        let low = before.by_symbol('L').unwrap();
+
+        let reordered = load(
+            r#"(kinds: [
+                (name: "low", symbol: 'L', points: 10, frames: [4, 5]),
+                (name: "high", symbol: 'H', points: 30, frames: [0, 1]),
+            ])"#,
+        )
+        .unwrap();
+        assert_eq!(reordered.get(&high).unwrap().points, 30);
+
+        let shrunk =
+            load(r#"(kinds: [(name: "high", symbol: 'H', points: 30, frames: [0])])"#).unwrap();
+        assert!(shrunk.get(&high).is_some());
+        assert!(shrunk.get(&low).is_none());
+    }
+
+    #[test]
+    fn names_and_symbols_must_be_unique() {
+        let same_name = r#"(kinds: [
+            (name: "high", symbol: 'H', points: 30, frames: [0]),
+            (name: "high", symbol: 'X', points: 30, frames: [0]),
+        ])"#;
+        assert!(matches!(
+            load(same_name),
+            Err(AlienKindError::DuplicateName(_))
+        ));
+
+        let same_symbol = r#"(kinds: [
+            (name: "high", symbol: 'H', points: 30, frames: [0]),
+            (name: "higher", symbol: 'H', points: 30, frames: [0]),
+        ])"#;
+        assert!(matches!(
+            load(same_symbol),
+            Err(AlienKindError::DuplicateSymbol('H'))
+        ));
+    }
+
+    #[test]
+    fn kinds_can_only_split_into_known_kinds() {
+        let ron = r#"(kinds: [(
+            name: "splitter",
+            symbol: 'S',
+            points: 40,
+            frames: [0],
+            splits_into: Some((kind: "nobody", count: 2)),
+        )])"#;
+        assert!(matches!(
+            load(ron),
+            Err(AlienKindError::UnknownSplitKind { .. })
+        ));
+    }
+}
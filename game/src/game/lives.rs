@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+
+use super::{
+    scoreboard::{Score, SCOREBOARD_FONT_SIZE, SCOREBOARD_TEXT_PADDING, TEXT_COLOR},
+    InGameMarker,
+};
+
+/// The number of ships the player starts the game with.
+const STARTING_LIVES: u32 = 3;
+/// The score at which the player is awarded an extra ship.
+const EXTRA_LIFE_SCORE: u32 = 1500;
+
+#[derive(Resource)]
+/// A global resource storing how many ships the player has left, including the
+/// one currently in play.
+pub struct Lives {
+    pub remaining: u32,
+    extra_life_awarded: bool,
+}
+
+impl Default for Lives {
+    fn default() -> Self {
+        Self {
+            remaining: STARTING_LIVES,
+            extra_life_awarded: false,
+        }
+    }
+}
+
+/// marker component for the lives display
+#[derive(Component, Default)]
+pub struct LivesDisplay;
+
+pub fn spawn_lives_display(commands: &mut Commands, font: Handle<Font>) {
+    commands.spawn((
+        LivesDisplay,
+        InGameMarker,
+        TextBundle::from_sections([
+            TextSection::new(
+                "Lives: ",
+                TextStyle {
+                    font_size: SCOREBOARD_FONT_SIZE,
+                    font: font.clone(),
+                    color: TEXT_COLOR,
+                    ..default()
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font_size: SCOREBOARD_FONT_SIZE,
+                font,
+                color: TEXT_COLOR,
+                ..default()
+            }),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: SCOREBOARD_TEXT_PADDING,
+            right: SCOREBOARD_TEXT_PADDING,
+            ..default()
+        }),
+    ));
+}
+
+pub fn update_sys(lives: Res<Lives>, mut query: Query<&mut Text, With<LivesDisplay>>) {
+    let mut text = query.single_mut();
+    text.sections[1].value = lives.remaining.to_string();
+}
+
+/// A system that awards the player one extra ship when their score first
+/// reaches [`EXTRA_LIFE_SCORE`].
+pub fn extra_life_sys(score: Res<Score>, mut lives: ResMut<Lives>) {
+    if !lives.extra_life_awarded && score.0 >= EXTRA_LIFE_SCORE {
+        lives.remaining += 1;
+        lives.extra_life_awarded = true;
+    }
+}
@@ -1,14 +1,40 @@
 pub mod aliens;
+pub mod bombs;
 pub mod collisions;
 pub mod explosions;
+pub mod formations;
 pub mod gameover;
+pub mod highscores;
+pub mod kinds;
+pub mod lives;
+pub mod multiplayer;
+pub mod pause;
 pub mod scoreboard;
 pub mod shields;
 pub mod ships;
+pub mod ufo;
+pub mod waves;
 
 use bevy::prelude::*;
 
-use self::{aliens::spawn_aliens, scoreboard::spawn_scoreboard, ships::PlayerShip};
+use self::{
+    aliens::{AlienFiring, AlienMovement, AlienVelocity, MarchBeat},
+    formations::{ActiveFormation, Formations, FORMATIONS_PATH},
+    highscores::spawn_high_score_display,
+    kinds::{AlienKinds, ALIEN_KINDS_PATH},
+    lives::{spawn_lives_display, Lives},
+    multiplayer::OnlineGame,
+    scoreboard::{spawn_scoreboard, Score},
+    shields::spawn_shields,
+    ships::{PlayerShip, ShotsFired},
+    ufo::UfoSpawner,
+    waves::{spawn_wave_display, Wave},
+};
+
+/// Marker component for every entity that belongs to a run of the game. These
+/// are all despawned when the run ends.
+#[derive(Component, Default)]
+pub struct InGameMarker;
 
 pub trait Spawnable: Component {
     fn spawn(pos: Vec3, texture_atlas: Handle<TextureAtlas>, commands: &mut Commands);
@@ -23,6 +49,7 @@ impl<T: AtlasIndexable + Default> Spawnable for T {
     fn spawn(pos: Vec3, texture_atlas: Handle<TextureAtlas>, commands: &mut Commands) {
         commands.spawn((
             T::default(),
+            InGameMarker,
             SpriteSheetBundle {
                 texture_atlas,
                 transform: Transform::from_translation(pos),
@@ -39,12 +66,19 @@ pub struct AssetHandles {
     pub font: Handle<Font>,
     pub shoot_sound: Handle<AudioSource>,
     pub explosion_sound: Handle<AudioSource>,
+    pub ufo_flying_sound: Handle<AudioSource>,
+    pub ufo_hit_sound: Handle<AudioSource>,
+    /// The four notes of the marching heartbeat, in the order they're played.
+    pub march_sounds: [Handle<AudioSource>; 4],
+    pub formations: Handle<Formations>,
+    pub alien_kinds: Handle<AlienKinds>,
 }
 
 pub fn setup_sys(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    online: Option<Res<OnlineGame>>,
 ) {
     let texture_handle = asset_server.load("sprites.png");
     let texture_atlas =
@@ -57,6 +91,16 @@ pub fn setup_sys(
         font: font.clone(),
         shoot_sound: asset_server.load("sfx/shoot.ogg"),
         explosion_sound: asset_server.load("sfx/explosion.ogg"),
+        ufo_flying_sound: asset_server.load("sfx/ufo_lowpitch.ogg"),
+        ufo_hit_sound: asset_server.load("sfx/ufo_highpitch.ogg"),
+        march_sounds: [
+            asset_server.load("sfx/fastinvader1.ogg"),
+            asset_server.load("sfx/fastinvader2.ogg"),
+            asset_server.load("sfx/fastinvader3.ogg"),
+            asset_server.load("sfx/fastinvader4.ogg"),
+        ],
+        formations: asset_server.load(FORMATIONS_PATH),
+        alien_kinds: asset_server.load(ALIEN_KINDS_PATH),
     });
 
     PlayerShip::spawn(
@@ -65,6 +109,33 @@ pub fn setup_sys(
         &mut commands,
     );
 
-    spawn_aliens(&mut commands, &texture_atlas_handle);
-    spawn_scoreboard(&mut commands, font);
+    // the aliens are spawned by formations::spawn_formation_sys once the
+    // formations file has loaded, or drawn from the server's snapshots online.
+    // Online games are played without the shields.
+    if online.is_none() {
+        spawn_shields(&mut commands);
+    }
+    spawn_scoreboard(&mut commands, font.clone());
+    spawn_high_score_display(&mut commands, font.clone());
+    spawn_lives_display(&mut commands, font.clone());
+    spawn_wave_display(&mut commands, font);
+}
+
+/// Despawns every entity from the last run and resets the global game
+/// resources, so that the next run starts from a fresh state.
+pub fn cleanup_sys(mut commands: Commands, entities: Query<Entity, With<InGameMarker>>) {
+    for e in &entities {
+        commands.entity(e).despawn_recursive();
+    }
+
+    commands.insert_resource(Score(0));
+    commands.insert_resource(Lives::default());
+    commands.insert_resource(AlienMovement::default());
+    commands.insert_resource(AlienVelocity::default());
+    commands.insert_resource(AlienFiring::default());
+    commands.insert_resource(MarchBeat::default());
+    commands.insert_resource(ShotsFired::default());
+    commands.insert_resource(UfoSpawner::default());
+    commands.insert_resource(Wave::default());
+    commands.insert_resource(ActiveFormation::default());
 }
@@ -0,0 +1,328 @@
+use bevy::prelude::*;
+use cosmos_raiders_protocol::{Event as ServerEvent, GameID, ShipSnapshot, Snapshot};
+use cosmos_raiders_sim::simulation::{BOMB_SPRITE_INDEX, LASER_SPRITE_INDEX, SHIP_Y};
+
+use super::{
+    bombs::ShipHitEvent,
+    explosions::Explosion,
+    lives::Lives,
+    scoreboard::Score,
+    ships::{fire_pressed, PlayerShip},
+    waves::Wave,
+    AssetHandles, AtlasIndexable, InGameMarker, Spawnable,
+};
+use crate::{
+    net::{NetClient, NetCommand, NetEvent, Session},
+    GameState,
+};
+
+/// How often the local ship's position is sent to the server, in seconds.
+const POSITION_SEND_INTERVAL_SECS: f32 = 0.05;
+const REMOTE_SHIP_COLOR: Color = Color::rgba(0.5, 1.0, 0.5, 0.6);
+
+#[derive(Resource, Debug)]
+/// A global resource storing the online game the player is in. The server
+/// runs the game, and the run only draws what it sends. The local ship is the
+/// only thing moved by the player.
+pub struct OnlineGame {
+    pub game_id: GameID,
+}
+
+/// Another player's ship, positioned from the server's snapshots.
+#[derive(Component, Debug)]
+pub struct RemoteShip {
+    pub name: String,
+}
+
+/// An alien, laser or bomb drawn from the server's snapshots, rather than
+/// simulated locally.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerSprite {
+    Alien,
+    Laser,
+    Bomb,
+}
+
+/// Throttles how often the local ship's position is sent to the server.
+#[derive(Resource)]
+pub struct PositionSender {
+    timer: Timer,
+    last_x: Option<f32>,
+}
+
+impl Default for PositionSender {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(POSITION_SEND_INTERVAL_SECS, TimerMode::Repeating),
+            last_x: None,
+        }
+    }
+}
+
+/// Run condition for systems that only run in online games.
+pub fn is_online(online: Option<Res<OnlineGame>>, client: Option<Res<NetClient>>) -> bool {
+    online.is_some() && client.is_some()
+}
+
+/// A system that sends the local ship's position to the server whenever it
+/// moves.
+pub fn send_position_sys(
+    time: Res<Time>,
+    mut sender: ResMut<PositionSender>,
+    client: Res<NetClient>,
+    ships: Query<&Transform, With<PlayerShip>>,
+) {
+    if !sender.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(trans) = ships.get_single() else {
+        return;
+    };
+    let x = trans.translation.x;
+    if sender.last_x == Some(x) {
+        return;
+    }
+    sender.last_x = Some(x);
+    client.send(NetCommand::UpdateX(x));
+}
+
+/// A system that asks the server to fire a laser from the local ship. The
+/// server decides whether the laser is fired, and it's drawn from the
+/// snapshots like everyone else's.
+pub fn shoot_sys(
+    keyboard_input: Res<Input<KeyCode>>,
+    button_inputs: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    client: Res<NetClient>,
+    ships: Query<(), With<PlayerShip>>,
+) {
+    if !ships.is_empty() && fire_pressed(&keyboard_input, &button_inputs, &gamepads) {
+        client.send(NetCommand::Shoot);
+    }
+}
+
+/// Returns the latest of the server's snapshots. Each snapshot is the whole
+/// state of the game, so only the latest one matters.
+fn latest_snapshot<'a>(net_events: &'a mut EventReader<NetEvent>) -> Option<&'a Snapshot> {
+    net_events
+        .iter()
+        .filter_map(|ev| match ev {
+            NetEvent::Server(ServerEvent::Snapshot(snapshot)) => Some(snapshot),
+            _ => None,
+        })
+        .last()
+}
+
+/// A system that spawns, moves and removes the other players' ships to match
+/// the server's snapshots.
+pub fn remote_ships_sys(
+    mut commands: Commands,
+    mut net_events: EventReader<NetEvent>,
+    session: Option<Res<Session>>,
+    asset_handles: Res<AssetHandles>,
+    mut remote_ships: Query<(Entity, &RemoteShip, &mut Transform, &mut Visibility)>,
+) {
+    let Some(snapshot) = latest_snapshot(&mut net_events) else {
+        return;
+    };
+    let own_name = session.as_ref().map(|s| s.name.as_str());
+    let others: Vec<&ShipSnapshot> = snapshot
+        .ships
+        .iter()
+        .filter(|ship| Some(ship.name.as_str()) != own_name)
+        .collect();
+
+    for (entity, remote, mut trans, mut visibility) in remote_ships.iter_mut() {
+        match others.iter().find(|ship| ship.name == remote.name) {
+            Some(ship) => {
+                trans.translation.x = ship.x;
+                // destroyed ships are hidden until they respawn
+                *visibility = if ship.in_play {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+            }
+            // the player left the game
+            None => commands.entity(entity).despawn(),
+        }
+    }
+    for ship in others {
+        if remote_ships
+            .iter()
+            .any(|(_, remote, _, _)| remote.name == ship.name)
+        {
+            continue;
+        }
+        commands.spawn((
+            RemoteShip {
+                name: ship.name.clone(),
+            },
+            InGameMarker,
+            SpriteSheetBundle {
+                texture_atlas: asset_handles.texture_atlas.clone(),
+                // drawn behind the local ship
+                transform: Transform::from_xyz(ship.x, SHIP_Y, -1.0),
+                sprite: TextureAtlasSprite {
+                    index: PlayerShip::SPRITE_INDEX,
+                    color: REMOTE_SHIP_COLOR,
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+    }
+}
+
+/// A system that draws the aliens, lasers and bombs in the server's
+/// snapshots. The sprites already on screen are moved to match each snapshot,
+/// and only the difference is spawned or despawned.
+pub fn server_sprites_sys(
+    mut commands: Commands,
+    mut net_events: EventReader<NetEvent>,
+    asset_handles: Res<AssetHandles>,
+    mut sprites: Query<(
+        Entity,
+        &ServerSprite,
+        &mut Transform,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    let Some(snapshot) = latest_snapshot(&mut net_events) else {
+        return;
+    };
+    let aliens = snapshot.aliens.iter().map(|alien| {
+        let pos = Vec2::new(alien.x, alien.y);
+        (ServerSprite::Alien, pos, alien.sprite_index as usize)
+    });
+    let lasers = snapshot.lasers.iter().map(|laser| {
+        let pos = Vec2::new(laser.x, laser.y);
+        (ServerSprite::Laser, pos, LASER_SPRITE_INDEX)
+    });
+    let bombs = snapshot.bombs.iter().map(|bomb| {
+        let pos = Vec2::new(bomb.x, bomb.y);
+        (ServerSprite::Bomb, pos, BOMB_SPRITE_INDEX)
+    });
+
+    let mut unused: Vec<_> = sprites.iter_mut().collect();
+    for (kind, pos, index) in aliens.chain(lasers).chain(bombs) {
+        match unused
+            .iter()
+            .position(|(_, used_for, _, _)| **used_for == kind)
+        {
+            Some(i) => {
+                let (_, _, mut trans, mut sprite) = unused.swap_remove(i);
+                trans.translation = pos.extend(0.0);
+                sprite.index = index;
+            }
+            None => {
+                commands.spawn((
+                    kind,
+                    InGameMarker,
+                    SpriteSheetBundle {
+                        texture_atlas: asset_handles.texture_atlas.clone(),
+                        transform: Transform::from_translation(pos.extend(0.0)),
+                        sprite: TextureAtlasSprite::new(index),
+                        ..default()
+                    },
+                ));
+            }
+        }
+    }
+    for (entity, _, _, _) in unused {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// A system that follows the server's events for the local player: their
+/// score, the wave, the aliens killed and their ship being destroyed.
+pub fn server_events_sys(
+    mut commands: Commands,
+    mut net_events: EventReader<NetEvent>,
+    session: Option<Res<Session>>,
+    asset_handles: Res<AssetHandles>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut wave: ResMut<Wave>,
+    ships: Query<&Transform, With<PlayerShip>>,
+    mut ship_hits: EventWriter<ShipHitEvent>,
+) {
+    let own_name = session.as_ref().map(|s| s.name.as_str());
+    for ev in net_events.iter() {
+        let NetEvent::Server(ev) = ev else {
+            continue;
+        };
+        match ev {
+            ServerEvent::Laser { by, .. } if Some(by.as_str()) == own_name => {
+                commands.spawn(AudioBundle {
+                    source: asset_handles.shoot_sound.clone(),
+                    settings: PlaybackSettings::DESPAWN,
+                });
+            }
+            ServerEvent::AlienKilled { x, y, .. } => {
+                commands.spawn(AudioBundle {
+                    source: asset_handles.explosion_sound.clone(),
+                    settings: PlaybackSettings::DESPAWN,
+                });
+                Explosion::spawn(
+                    Vec3::new(*x, *y, 0.0),
+                    asset_handles.texture_atlas.clone(),
+                    &mut commands,
+                );
+            }
+            ServerEvent::ScoreChanged {
+                name,
+                score: new_score,
+            } if Some(name.as_str()) == own_name => {
+                score.0 = *new_score;
+            }
+            ServerEvent::WaveStarted { wave: number } => wave.number = *number,
+            ServerEvent::PlayerDied {
+                name,
+                lives: remaining,
+            } if Some(name.as_str()) == own_name => {
+                lives.remaining = *remaining;
+                if let Ok(ship) = ships.get_single() {
+                    ship_hits.send(ShipHitEvent {
+                        position: ship.translation.truncate(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A system that ends the run when the server's game is over. The player
+/// leaves the finished game straight away, so that retrying starts a single
+/// player run.
+pub fn game_over_sys(
+    mut commands: Commands,
+    mut net_events: EventReader<NetEvent>,
+    client: Res<NetClient>,
+    score: Res<Score>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let game_over = net_events
+        .iter()
+        .any(|ev| matches!(ev, NetEvent::Server(ServerEvent::GameOver)));
+    if !game_over {
+        return;
+    }
+    // online scores go on the server's leaderboards rather than the local high
+    // score table
+    info!("Online game over - score {}", score.0);
+    client.send(NetCommand::LeaveGame);
+    leave(&mut commands);
+    next_state.set(GameState::GameOver);
+}
+
+/// Leaves the online game, so that the next run is a single player one.
+pub fn leave_sys(mut commands: Commands) {
+    leave(&mut commands);
+}
+
+fn leave(commands: &mut Commands) {
+    commands.remove_resource::<OnlineGame>();
+    commands.insert_resource(PositionSender::default());
+}
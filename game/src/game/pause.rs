@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use crate::PauseState;
+
+/// Sent to throw the current run away and start a new one. Setting
+/// `GameState::InGame` while already in it doesn't run the
+/// `OnEnter(GameState::InGame)` systems, so a restart from the pause menu runs
+/// them itself when it sees this event.
+#[derive(Event)]
+pub struct RestartEvent;
+
+/// A system that pauses and resumes the game when Escape, P or the gamepad's
+/// Start button is pressed.
+pub fn toggle_sys(
+    keyboard_input: Res<Input<KeyCode>>,
+    button_inputs: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    let kbd_pressed =
+        keyboard_input.just_pressed(KeyCode::Escape) || keyboard_input.just_pressed(KeyCode::P);
+    let gamepad_pressed = gamepads
+        .iter()
+        .any(|gp| button_inputs.just_pressed(GamepadButton::new(gp, GamepadButtonType::Start)));
+    if !(kbd_pressed || gamepad_pressed) {
+        return;
+    }
+
+    next_state.set(match state.get() {
+        PauseState::Running => PauseState::Paused,
+        PauseState::Paused => PauseState::Running,
+    });
+}
+
+/// Freezes everything that isn't already held by the `PauseState::Running` run
+/// condition. Pausing [`Time`] stops every timer in the game where it is, and
+/// pausing the audio sinks stops sounds that are already playing, like the
+/// UFO's drone.
+pub fn freeze_sys(mut time: ResMut<Time>, sinks: Query<&AudioSink>) {
+    time.pause();
+    for sink in &sinks {
+        sink.pause();
+    }
+}
+
+/// Undoes [`freeze_sys`].
+pub fn unfreeze_sys(mut time: ResMut<Time>, sinks: Query<&AudioSink>) {
+    time.unpause();
+    for sink in &sinks {
+        sink.play();
+    }
+}
@@ -1,12 +1,17 @@
 use bevy::prelude::*;
 
-const SCOREBOARD_FONT_SIZE: f32 = 32.0;
-const SCOREBOARD_TEXT_PADDING: Val = Val::Px(36.0);
-const TEXT_COLOR: Color = Color::rgb(1.0, 1.0, 1.0);
+use super::InGameMarker;
+
+pub const SCOREBOARD_FONT_SIZE: f32 = 32.0;
+/// How far the scoreboard text is from the edges of the screen, in pixels.
+pub const SCOREBOARD_PADDING_PX: f32 = 36.0;
+pub const SCOREBOARD_TEXT_PADDING: Val = Val::Px(SCOREBOARD_PADDING_PX);
+pub const TEXT_COLOR: Color = Color::rgb(1.0, 1.0, 1.0);
 
 pub fn spawn_scoreboard(commands: &mut Commands, font: Handle<Font>) {
     commands.spawn((
         Scoreboard,
+        InGameMarker,
         TextBundle::from_sections([
             TextSection::new(
                 "Score: ",
@@ -44,3 +49,54 @@ pub fn update_sys(score: Res<Score>, mut query: Query<(With<Scoreboard>, &mut Te
     let mut text = query.single_mut().1;
     text.sections[1].value = score.0.to_string();
 }
+
+/// How long a score popup stays on screen.
+const POPUP_SECS: f32 = 1.0;
+/// How fast a score popup floats upwards, in pixels per second.
+const POPUP_VELOCITY: f32 = 30.0;
+const POPUP_FONT_SIZE: f32 = 16.0;
+
+/// A number that floats up from where points were scored, then fades away.
+#[derive(Component)]
+pub struct ScorePopup {
+    timer: Timer,
+}
+
+pub fn spawn_score_popup(commands: &mut Commands, font: Handle<Font>, pos: Vec2, points: u32) {
+    commands.spawn((
+        ScorePopup {
+            timer: Timer::from_seconds(POPUP_SECS, TimerMode::Once),
+        },
+        InGameMarker,
+        Text2dBundle {
+            text: Text::from_section(
+                points.to_string(),
+                TextStyle {
+                    font_size: POPUP_FONT_SIZE,
+                    font,
+                    color: TEXT_COLOR,
+                },
+            ),
+            transform: Transform::from_translation(pos.extend(1.0)),
+            ..default()
+        },
+    ));
+}
+
+pub fn score_popup_sys(
+    time: Res<Time>,
+    mut popups: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>,
+    mut commands: Commands,
+) {
+    for (entity, mut popup, mut trans, mut text) in popups.iter_mut() {
+        if popup.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        trans.translation.y += POPUP_VELOCITY * time.delta_seconds();
+        let alpha = popup.timer.percent_left();
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
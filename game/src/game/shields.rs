@@ -1,6 +1,28 @@
 use bevy::prelude::*;
 
-const SHIELD_STRUCTURE: [[u8; 24]; 24] = [
+use super::{
+    aliens::Alien,
+    collisions::{collide_rect, sprite_bounds, CollisionMatrices},
+    AtlasIndexable, InGameMarker,
+};
+
+/// The number of shields spawned between the player and the aliens.
+const SHIELD_COUNT: usize = 4;
+/// The horizontal distance between the centres of neighbouring shields.
+const SHIELD_SPACING_X: f32 = 150.0;
+/// The y position of the centre of every shield.
+const SHIELD_Y: f32 = -80.0;
+/// How many logical pixels wide and tall a single shield pixel is drawn.
+const SHIELD_PIXEL_SIZE: f32 = 2.0;
+/// The width and height of [`SHIELD_STRUCTURE`] in shield pixels.
+const SHIELD_SIZE: usize = 24;
+const SHIELD_COLOR: Color = Color::rgb(0.0, 1.0, 0.0);
+/// The radius, in logical pixels, around a projectile's point of impact in
+/// which shield pixels are damaged. Pixels at the centre of the blast are
+/// destroyed outright, pixels further out are only weakened.
+const BLAST_RADIUS: f32 = 6.0;
+
+const SHIELD_STRUCTURE: [[u8; SHIELD_SIZE]; SHIELD_SIZE] = [
     [
         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     ],
@@ -75,8 +97,173 @@ const SHIELD_STRUCTURE: [[u8; 24]; 24] = [
     ],
 ];
 
+/// A single shield. The shield itself isn't drawn - it only stores the area
+/// covered by its pixels so that collision systems can skip the per-pixel
+/// checks for anything that isn't near a shield.
 #[derive(Component)]
-struct ShieldPixel {
+pub struct Shield {
+    bounds: Rect,
+}
+
+#[derive(Component)]
+pub struct ShieldPixel {
     /// Health is given between 0 and 255 where 0 is 0 and 255 is 100% health
     health: u8,
 }
+
+impl ShieldPixel {
+    /// Reduces the health of the pixel. Returns true if the pixel has been
+    /// destroyed.
+    fn damage(&mut self, amount: u8) -> bool {
+        self.health = self.health.saturating_sub(amount);
+        self.health == 0
+    }
+
+    fn color(&self) -> Color {
+        SHIELD_COLOR.with_a(self.health as f32 / u8::MAX as f32)
+    }
+}
+
+/// Returns the area covered by a shield pixel centred at `pos`.
+fn pixel_bounds(pos: Vec2) -> Rect {
+    Rect::from_center_size(pos, Vec2::splat(SHIELD_PIXEL_SIZE))
+}
+
+/// A procedure that spawns all the shields in the game.
+pub fn spawn_shields(commands: &mut Commands) {
+    for i in 0..SHIELD_COUNT {
+        let offset = i as f32 - (SHIELD_COUNT - 1) as f32 / 2.0;
+        spawn_shield(commands, Vec2::new(offset * SHIELD_SPACING_X, SHIELD_Y));
+    }
+}
+
+fn spawn_shield(commands: &mut Commands, center: Vec2) {
+    let size = SHIELD_SIZE as f32 * SHIELD_PIXEL_SIZE;
+    commands.spawn((
+        Shield {
+            bounds: Rect::from_center_size(center, Vec2::splat(size)),
+        },
+        InGameMarker,
+    ));
+
+    // SHIELD_STRUCTURE is laid out top to bottom, so start from the top left
+    // corner and work downwards
+    let top_left = center + Vec2::new(-size / 2.0, size / 2.0);
+    for (row, cells) in SHIELD_STRUCTURE.iter().enumerate() {
+        for (col, &cell) in cells.iter().enumerate() {
+            if cell == 0 {
+                continue;
+            }
+            let pos = top_left
+                + Vec2::new(
+                    (col as f32 + 0.5) * SHIELD_PIXEL_SIZE,
+                    -(row as f32 + 0.5) * SHIELD_PIXEL_SIZE,
+                );
+            let pixel = ShieldPixel { health: u8::MAX };
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: pixel.color(),
+                        custom_size: Some(Vec2::splat(SHIELD_PIXEL_SIZE)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(pos.extend(0.0)),
+                    ..default()
+                },
+                pixel,
+                InGameMarker,
+            ));
+        }
+    }
+}
+
+/// A system that erodes shields when they are hit by a projectile of type `P`.
+/// The projectile is despawned and every pixel within [`BLAST_RADIUS`] of the
+/// pixel it hit is damaged, with pixels closer to the impact taking more
+/// damage.
+pub fn projectile_collision_sys<P: AtlasIndexable>(
+    projectiles: Query<(Entity, &Transform), With<P>>,
+    shields: Query<&Shield>,
+    mut pixels: Query<(Entity, &Transform, &mut ShieldPixel, &mut Sprite)>,
+    matrices: Res<CollisionMatrices>,
+    mut commands: Commands,
+) {
+    for (projectile_entity, projectile_transform) in projectiles.iter() {
+        let projectile_pos = projectile_transform.translation.truncate();
+        let projectile_bounds = sprite_bounds(projectile_pos);
+
+        // skip the per-pixel checks if the projectile isn't near any shield
+        if shields
+            .iter()
+            .all(|shield| shield.bounds.intersect(projectile_bounds).is_empty())
+        {
+            continue;
+        }
+
+        let impact = pixels
+            .iter()
+            .filter(|(_, _, pixel, _)| pixel.health > 0)
+            .map(|(_, transform, _, _)| transform.translation.truncate())
+            .find(|&pixel_pos| {
+                collide_rect(
+                    &matrices,
+                    P::SPRITE_INDEX,
+                    projectile_pos,
+                    pixel_bounds(pixel_pos),
+                )
+            });
+        let Some(impact_pos) = impact else {
+            continue;
+        };
+
+        commands.entity(projectile_entity).despawn();
+
+        for (pixel_entity, transform, mut pixel, mut sprite) in pixels.iter_mut() {
+            if pixel.health == 0 {
+                continue;
+            }
+            let distance = transform.translation.truncate().distance(impact_pos);
+            if distance > BLAST_RADIUS {
+                continue;
+            }
+            let falloff = 1.0 - 0.75 * distance / BLAST_RADIUS;
+            if pixel.damage((u8::MAX as f32 * falloff) as u8) {
+                commands.entity(pixel_entity).despawn();
+            } else {
+                sprite.color = pixel.color();
+            }
+        }
+    }
+}
+
+/// A system that removes any shield pixels that aliens march into.
+pub fn alien_collision_sys(
+    aliens: Query<(&Transform, &TextureAtlasSprite), With<Alien>>,
+    shields: Query<&Shield>,
+    mut pixels: Query<(Entity, &Transform, &mut ShieldPixel)>,
+    matrices: Res<CollisionMatrices>,
+    mut commands: Commands,
+) {
+    for (alien_transform, sprite) in aliens.iter() {
+        let alien_pos = alien_transform.translation.truncate();
+        let alien_bounds = sprite_bounds(alien_pos);
+
+        if shields
+            .iter()
+            .all(|shield| shield.bounds.intersect(alien_bounds).is_empty())
+        {
+            continue;
+        }
+
+        for (pixel_entity, transform, mut pixel) in pixels.iter_mut() {
+            if pixel.health == 0 {
+                continue;
+            }
+            let pixel_pos = transform.translation.truncate();
+            if collide_rect(&matrices, sprite.index, alien_pos, pixel_bounds(pixel_pos)) {
+                pixel.health = 0;
+                commands.entity(pixel_entity).despawn();
+            }
+        }
+    }
+}
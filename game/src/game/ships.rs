@@ -1,6 +1,48 @@
-use bevy::{audio::PlaybackMode, prelude::*, window::PrimaryWindow};
+use std::time::Duration;
 
-use super::{AssetHandles, AtlasIndexable, Spawnable};
+use bevy::{
+    audio::PlaybackMode,
+    input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use cosmos_raiders_sim::simulation::{LASER_SPRITE_INDEX, LASER_VELOCITY, SHIP_SPRITE_INDEX};
+
+use super::{
+    bombs::ShipHitEvent, explosions::Explosion, lives::Lives, multiplayer::OnlineGame,
+    AssetHandles, AtlasIndexable, Spawnable,
+};
+
+#[derive(Resource, Default)]
+/// A global resource counting the lasers the player has fired. The UFO uses
+/// this to decide how many points it's worth.
+pub struct ShotsFired(pub u32);
+
+/// Whether the player pressed the fire button this frame, on the keyboard or
+/// the first gamepad.
+pub fn fire_pressed(
+    keyboard_input: &Input<KeyCode>,
+    button_inputs: &Input<GamepadButton>,
+    gamepads: &Gamepads,
+) -> bool {
+    let gamepad_fired = if let Some(gp) = gamepads.iter().nth(0) {
+        let trigger_down =
+            button_inputs.just_pressed(GamepadButton::new(gp, GamepadButtonType::RightTrigger2));
+
+        let button_down =
+            button_inputs.just_pressed(GamepadButton::new(gp, GamepadButtonType::South));
+
+        trigger_down || button_down
+    } else {
+        false
+    };
+
+    let kbd_fired =
+        keyboard_input.just_pressed(KeyCode::Space) || keyboard_input.just_pressed(KeyCode::Return);
+
+    kbd_fired || gamepad_fired
+}
 
 #[derive(Component, Default)]
 pub struct PlayerShip {
@@ -8,7 +50,7 @@ pub struct PlayerShip {
 }
 
 impl AtlasIndexable for PlayerShip {
-    const SPRITE_INDEX: usize = 13;
+    const SPRITE_INDEX: usize = SHIP_SPRITE_INDEX;
 }
 
 impl PlayerShip {
@@ -112,28 +154,15 @@ impl PlayerShip {
         player_ships: Query<(&mut Transform, &Handle<TextureAtlas>), With<PlayerShip>>,
         lasers: Query<(), With<Laser>>,
         asset_handles: Res<AssetHandles>,
+        mut shots_fired: ResMut<ShotsFired>,
     ) {
         for (trans, atlas_handle) in player_ships.iter() {
-            let gamepad_fired = if let Some(gp) = gamepads.iter().nth(0) {
-                let trigger_down = button_inputs
-                    .just_pressed(GamepadButton::new(gp, GamepadButtonType::RightTrigger2));
-
-                let button_down =
-                    button_inputs.just_pressed(GamepadButton::new(gp, GamepadButtonType::South));
-
-                trigger_down || button_down
-            } else {
-                false
-            };
-
-            let kbd_fired = keyboard_input.just_pressed(KeyCode::Space)
-                || keyboard_input.just_pressed(KeyCode::Return);
-
-            if kbd_fired || gamepad_fired {
+            if fire_pressed(&keyboard_input, &button_inputs, &gamepads) {
                 if lasers.iter().count() > 0 {
                     return;
                 }
                 PlayerShip::fire_laser(&mut commands, trans.translation, atlas_handle.clone());
+                shots_fired.0 += 1;
                 commands.spawn(AudioBundle {
                     source: asset_handles.shoot_sound.clone(),
                     settings: PlaybackSettings {
@@ -144,21 +173,176 @@ impl PlayerShip {
             }
         }
     }
+
+    /// A system that destroys the player's ship when it's hit by a bomb. The
+    /// ship is turned into a [`ShipWreck`], which stops it from moving,
+    /// firing or being hit again until it respawns.
+    pub fn hit_sys(
+        mut ship_hits: EventReader<ShipHitEvent>,
+        mut ships: Query<(Entity, &mut TextureAtlasSprite), With<PlayerShip>>,
+        mut lives: ResMut<Lives>,
+        online: Option<Res<OnlineGame>>,
+        mut commands: Commands,
+        asset_handles: Res<AssetHandles>,
+        mut rumble_requests: EventWriter<GamepadRumbleRequest>,
+        gamepads: Res<Gamepads>,
+    ) {
+        if ship_hits.iter().count() == 0 {
+            return;
+        }
+        let Ok((entity, mut sprite)) = ships.get_single_mut() else {
+            return;
+        };
+
+        // online games count lives on the server
+        if online.is_none() {
+            lives.remaining = lives.remaining.saturating_sub(1);
+        }
+        sprite.index = ShipWreck::SPRITE_INDICES[0];
+        commands
+            .entity(entity)
+            .remove::<PlayerShip>()
+            .insert(ShipWreck::default());
+        commands.spawn(AudioBundle {
+            source: asset_handles.explosion_sound.clone(),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                ..default()
+            },
+        });
+        for gamepad in gamepads.iter() {
+            rumble_requests.send(GamepadRumbleRequest::Add {
+                gamepad,
+                intensity: GamepadRumbleIntensity::STRONG_MAX,
+                duration: Duration::from_millis(1000),
+            });
+        }
+    }
+}
+
+/// The remains of the player's ship after it has been destroyed. The wreck
+/// plays the death animation, then stays hidden until the ship respawns.
+#[derive(Component)]
+pub struct ShipWreck {
+    frame_timer: Timer,
+    respawn_timer: Timer,
+}
+
+impl Default for ShipWreck {
+    fn default() -> Self {
+        Self {
+            frame_timer: Timer::from_seconds(0.1, TimerMode::Repeating),
+            respawn_timer: Timer::from_seconds(ShipWreck::RESPAWN_DELAY_SECS, TimerMode::Once),
+        }
+    }
+}
+
+impl ShipWreck {
+    /// The death animation alternates between the ship's debris and the
+    /// explosion the aliens leave behind.
+    const SPRITE_INDICES: [usize; 2] = [8, Explosion::SPRITE_INDEX];
+    const ANIMATION_SECS: f32 = 1.2;
+    const RESPAWN_DELAY_SECS: f32 = 2.5;
+
+    /// A system that animates the wreck, then respawns the ship if the player
+    /// has any lives left. Otherwise the wreck is removed for good.
+    pub fn respawn_sys(
+        time: Res<Time>,
+        mut wrecks: Query<(
+            Entity,
+            &mut ShipWreck,
+            &mut TextureAtlasSprite,
+            &mut Visibility,
+            &mut Transform,
+        )>,
+        lives: Res<Lives>,
+        mut commands: Commands,
+    ) {
+        for (entity, mut wreck, mut sprite, mut visibility, mut trans) in wrecks.iter_mut() {
+            wreck.respawn_timer.tick(time.delta());
+
+            if wreck.respawn_timer.elapsed_secs() < ShipWreck::ANIMATION_SECS {
+                if wreck.frame_timer.tick(time.delta()).just_finished() {
+                    sprite.index = if sprite.index == ShipWreck::SPRITE_INDICES[0] {
+                        ShipWreck::SPRITE_INDICES[1]
+                    } else {
+                        ShipWreck::SPRITE_INDICES[0]
+                    };
+                }
+            } else {
+                *visibility = Visibility::Hidden;
+            }
+
+            if !wreck.respawn_timer.finished() {
+                continue;
+            }
+
+            if lives.remaining == 0 {
+                commands.entity(entity).despawn();
+                continue;
+            }
+
+            sprite.index = PlayerShip::SPRITE_INDEX;
+            trans.translation.x = 0.0;
+            *visibility = Visibility::Visible;
+            commands
+                .entity(entity)
+                .remove::<ShipWreck>()
+                .insert((PlayerShip::default(), Invulnerable::default()));
+        }
+    }
+}
+
+/// Makes a freshly respawned ship immune to bombs for a short time. The ship
+/// blinks while the invulnerability lasts.
+#[derive(Component)]
+pub struct Invulnerable {
+    timer: Timer,
+    blink_timer: Timer,
+}
+
+impl Default for Invulnerable {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(2.0, TimerMode::Once),
+            blink_timer: Timer::from_seconds(0.1, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Invulnerable {
+    pub fn blink_sys(
+        time: Res<Time>,
+        mut ships: Query<(Entity, &mut Invulnerable, &mut Visibility)>,
+        mut commands: Commands,
+    ) {
+        for (entity, mut invulnerable, mut visibility) in ships.iter_mut() {
+            if invulnerable.timer.tick(time.delta()).finished() {
+                *visibility = Visibility::Visible;
+                commands.entity(entity).remove::<Invulnerable>();
+                continue;
+            }
+            if invulnerable.blink_timer.tick(time.delta()).just_finished() {
+                *visibility = match *visibility {
+                    Visibility::Hidden => Visibility::Visible,
+                    _ => Visibility::Hidden,
+                };
+            }
+        }
+    }
 }
 
 #[derive(Component, Default)]
 pub struct Laser;
 
 impl AtlasIndexable for Laser {
-    const SPRITE_INDEX: usize = 10;
+    const SPRITE_INDEX: usize = LASER_SPRITE_INDEX;
 }
 
 impl Laser {
-    const VELOCITY: f32 = 480.0; // pixels per second
-
     /// Update the laser's position according to the frame delta time
     fn update_position(&mut self, dt: f32, pos: &mut Vec3) {
-        pos.y += Laser::VELOCITY * dt;
+        pos.y += LASER_VELOCITY * dt;
     }
 
     /// Despawn the laser if it goes off the top of the screen
@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use bevy::{audio::PlaybackMode, prelude::*};
+use rand::Rng;
+
+use super::{
+    aliens::Alien,
+    collisions::{collide, CollisionMatrices},
+    scoreboard::{spawn_score_popup, Score},
+    ships::{Laser, ShotsFired},
+    AssetHandles, AtlasIndexable, InGameMarker,
+};
+
+/// The UFO is twice as wide as the other sprites, so it's split across two
+/// neighbouring sprites in the texture atlas.
+const LEFT_SPRITE_INDEX: usize = 6;
+const RIGHT_SPRITE_INDEX: usize = 7;
+/// How far the centre of each half is from the centre of the UFO.
+const HALF_OFFSET_X: f32 = 16.0;
+/// The UFO starts just off screen and is despawned once it's back off screen on
+/// the other side.
+const START_X: f32 = 380.0;
+const FLIGHT_Y: f32 = 260.0;
+const VELOCITY: f32 = 120.0; // pixels per second
+/// The UFO appears at a random interval between these two values, in seconds.
+const MIN_SPAWN_INTERVAL: f32 = 15.0;
+const MAX_SPAWN_INTERVAL: f32 = 30.0;
+/// The UFO stops appearing once the formation is this small, as in the arcade
+/// original.
+const MIN_ALIENS_FOR_SPAWN: usize = 8;
+
+/// The points awarded for shooting the UFO, indexed by the number of shots the
+/// player has fired. This is the table used by the arcade original, which is
+/// what makes the famous 300 point trick shots possible.
+const SCORE_TABLE: [u32; 15] = [
+    100, 50, 50, 100, 150, 100, 100, 50, 300, 100, 100, 100, 50, 150, 100,
+];
+
+#[derive(Component)]
+pub struct Ufo {
+    /// -1 when flying left, 1 when flying right.
+    direction: f32,
+}
+
+#[derive(Resource)]
+/// A global resource storing how long it is until the next UFO appears.
+pub struct UfoSpawner {
+    timer: Timer,
+}
+
+impl Default for UfoSpawner {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(random_spawn_interval(), TimerMode::Once),
+        }
+    }
+}
+
+fn random_spawn_interval() -> Duration {
+    Duration::from_secs_f32(rand::thread_rng().gen_range(MIN_SPAWN_INTERVAL..MAX_SPAWN_INTERVAL))
+}
+
+/// Returns the number of points the UFO is worth after `shots_fired` shots.
+fn ufo_points(shots_fired: u32) -> u32 {
+    SCORE_TABLE[shots_fired as usize % SCORE_TABLE.len()]
+}
+
+fn spawn_ufo(commands: &mut Commands, asset_handles: &AssetHandles, direction: f32) {
+    commands
+        .spawn((
+            Ufo { direction },
+            InGameMarker,
+            SpatialBundle::from_transform(Transform::from_xyz(-direction * START_X, FLIGHT_Y, 0.0)),
+            AudioBundle {
+                source: asset_handles.ufo_flying_sound.clone(),
+                settings: PlaybackSettings::LOOP,
+            },
+        ))
+        .with_children(|parent| {
+            for (sprite_index, offset_x) in [
+                (LEFT_SPRITE_INDEX, -HALF_OFFSET_X),
+                (RIGHT_SPRITE_INDEX, HALF_OFFSET_X),
+            ] {
+                parent.spawn(SpriteSheetBundle {
+                    texture_atlas: asset_handles.texture_atlas.clone(),
+                    transform: Transform::from_xyz(offset_x, 0.0, 0.0),
+                    sprite: TextureAtlasSprite::new(sprite_index),
+                    ..default()
+                });
+            }
+        });
+}
+
+/// A system that sends a UFO across the top of the screen at random intervals.
+pub fn spawn_sys(
+    time: Res<Time>,
+    mut spawner: ResMut<UfoSpawner>,
+    ufos: Query<(), With<Ufo>>,
+    aliens: Query<(), With<Alien>>,
+    shots_fired: Res<ShotsFired>,
+    asset_handles: Res<AssetHandles>,
+    mut commands: Commands,
+) {
+    // the timer only starts again once the last UFO has gone
+    if !ufos.is_empty() {
+        return;
+    }
+    if !spawner.timer.tick(time.delta()).finished() {
+        return;
+    }
+    if aliens.iter().count() < MIN_ALIENS_FOR_SPAWN {
+        return;
+    }
+
+    // like the original, the UFO enters from the right when the player has fired an
+    // even number of shots
+    let direction = if shots_fired.0 % 2 == 0 { -1.0 } else { 1.0 };
+    spawn_ufo(&mut commands, &asset_handles, direction);
+    spawner.timer = Timer::new(random_spawn_interval(), TimerMode::Once);
+}
+
+pub fn movement_sys(
+    time: Res<Time>,
+    mut ufos: Query<(Entity, &Ufo, &mut Transform)>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_seconds();
+    for (entity, ufo, mut trans) in ufos.iter_mut() {
+        trans.translation.x += ufo.direction * VELOCITY * dt;
+        if trans.translation.x.abs() > START_X {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn laser_collision_sys(
+    lasers: Query<(Entity, &Transform), With<Laser>>,
+    ufos: Query<(Entity, &Transform), With<Ufo>>,
+    matrices: Res<CollisionMatrices>,
+    mut score: ResMut<Score>,
+    shots_fired: Res<ShotsFired>,
+    asset_handles: Res<AssetHandles>,
+    mut commands: Commands,
+) {
+    for (ufo_entity, ufo_transform) in ufos.iter() {
+        let ufo_pos = ufo_transform.translation.truncate();
+        let left_pos = ufo_pos - Vec2::new(HALF_OFFSET_X, 0.0);
+        let right_pos = ufo_pos + Vec2::new(HALF_OFFSET_X, 0.0);
+
+        for (laser_entity, laser_transform) in lasers.iter() {
+            let laser_pos = laser_transform.translation.truncate();
+            let hit = collide(
+                &matrices,
+                Laser::SPRITE_INDEX,
+                LEFT_SPRITE_INDEX,
+                laser_pos,
+                left_pos,
+            ) || collide(
+                &matrices,
+                Laser::SPRITE_INDEX,
+                RIGHT_SPRITE_INDEX,
+                laser_pos,
+                right_pos,
+            );
+            if !hit {
+                continue;
+            }
+
+            let points = ufo_points(shots_fired.0);
+            score.0 += points;
+            commands.entity(ufo_entity).despawn_recursive();
+            commands.entity(laser_entity).despawn();
+            commands.spawn(AudioBundle {
+                source: asset_handles.ufo_hit_sound.clone(),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    ..default()
+                },
+            });
+            spawn_score_popup(&mut commands, asset_handles.font.clone(), ufo_pos, points);
+            break;
+        }
+    }
+}
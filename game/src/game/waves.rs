@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+
+use super::{
+    scoreboard::{SCOREBOARD_FONT_SIZE, SCOREBOARD_TEXT_PADDING, TEXT_COLOR},
+    InGameMarker,
+};
+
+/// How much lower each new wave starts than the one before it.
+const WAVE_DROP_Y: f32 = 16.0;
+/// Like the arcade original, the starting height goes back to the top after
+/// this many waves. Any lower and the formation would start on top of the
+/// shields.
+const DROP_CYCLE: u32 = 6;
+/// How much faster the formation marches on each wave, as a fraction of the
+/// first wave's velocity.
+const VELOCITY_INCREASE_PER_WAVE: f32 = 0.1;
+/// How long the "WAVE N" banner is shown before play resumes.
+const INTERSTITIAL_SECS: f32 = 2.0;
+const BANNER_FONT_SIZE: f32 = 48.0;
+
+#[derive(Resource)]
+/// A global resource storing which wave the player is on.
+pub struct Wave {
+    pub number: u32,
+    /// Whether this wave's formation has been spawned yet. The formation can
+    /// only be spawned once the formations file has loaded.
+    pub formation_spawned: bool,
+    interstitial: Timer,
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            number: 1,
+            formation_spawned: false,
+            interstitial: Timer::from_seconds(INTERSTITIAL_SECS, TimerMode::Once),
+        }
+    }
+}
+
+impl Wave {
+    /// How far below its usual starting height the formation starts on this
+    /// wave.
+    pub fn drop_y(&self) -> f32 {
+        ((self.number - 1) % DROP_CYCLE) as f32 * WAVE_DROP_Y
+    }
+
+    /// How much faster the formation marches on this wave than on the first.
+    pub fn velocity_multiplier(&self) -> f32 {
+        1.0 + (self.number - 1) as f32 * VELOCITY_INCREASE_PER_WAVE
+    }
+
+    /// Moves on to the next wave, showing its banner before play resumes.
+    pub fn advance(&mut self) {
+        self.number += 1;
+        self.formation_spawned = false;
+        self.interstitial.reset();
+    }
+}
+
+/// A run condition that is false while the "WAVE N" banner is showing, or
+/// while the wave's formation hasn't been spawned yet.
+pub fn wave_in_play(wave: Res<Wave>) -> bool {
+    wave.interstitial.finished() && wave.formation_spawned
+}
+
+/// marker component for the wave display
+#[derive(Component, Default)]
+pub struct WaveDisplay;
+
+/// marker component for the "WAVE N" banner shown between waves
+#[derive(Component, Default)]
+pub struct WaveBanner;
+
+pub fn spawn_wave_display(commands: &mut Commands, font: Handle<Font>) {
+    commands.spawn((
+        WaveDisplay,
+        InGameMarker,
+        TextBundle::from_sections([
+            TextSection::new(
+                "Wave: ",
+                TextStyle {
+                    font_size: SCOREBOARD_FONT_SIZE,
+                    font: font.clone(),
+                    color: TEXT_COLOR,
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font_size: SCOREBOARD_FONT_SIZE,
+                font: font.clone(),
+                color: TEXT_COLOR,
+            }),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: SCOREBOARD_TEXT_PADDING,
+            left: SCOREBOARD_TEXT_PADDING,
+            ..default()
+        }),
+    ));
+
+    commands.spawn((
+        WaveBanner,
+        InGameMarker,
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: BANNER_FONT_SIZE,
+                    font,
+                    color: TEXT_COLOR,
+                },
+            ),
+            transform: Transform::from_xyz(0.0, 0.0, 1.0),
+            ..default()
+        },
+    ));
+}
+
+pub fn update_sys(wave: Res<Wave>, mut query: Query<&mut Text, With<WaveDisplay>>) {
+    let mut text = query.single_mut();
+    text.sections[1].value = wave.number.to_string();
+}
+
+/// A system that counts down the banner shown at the start of every wave.
+pub fn interstitial_sys(
+    time: Res<Time>,
+    mut wave: ResMut<Wave>,
+    mut banners: Query<(&mut Text, &mut Visibility), With<WaveBanner>>,
+) {
+    if wave.interstitial.finished() {
+        return;
+    }
+    wave.interstitial.tick(time.delta());
+    let showing = !wave.interstitial.finished();
+
+    for (mut text, mut visibility) in banners.iter_mut() {
+        text.sections[0].value = format!("WAVE {}", wave.number);
+        *visibility = if showing {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
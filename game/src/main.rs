@@ -1,4 +1,4 @@
-use bevy::time::common_conditions::on_timer;
+use bevy::asset::ChangeWatcher;
 use bevy::window::PresentMode;
 use bevy::{prelude::*, window::WindowResolution};
 use bevy_framepace::FramepacePlugin;
@@ -7,82 +7,315 @@ use bevy_screen_diagnostics::{ScreenDiagnosticsPlugin, ScreenFrameDiagnosticsPlu
 use bevy_spatial::{AutomaticUpdate, SpatialStructure, TransformMode};
 use bevy_tokio_tasks::TokioTasksPlugin;
 use game::{
-    aliens::{AlienMovement, AlienVelocity, HighLevelAlien, LowLevelAlien, MidLevelAlien},
+    aliens::{Alien, AlienFiring, AlienMovement, AlienVelocity, MarchBeat, MarchStepEvent},
+    bombs::ShipHitEvent,
     collisions::load_collision_matrices,
+    formations::{ActiveFormation, Formations, FormationsLoader},
+    highscores::load_high_scores,
+    kinds::{AlienKinds, AlienKindsLoader},
+    lives::Lives,
+    multiplayer::PositionSender,
     scoreboard::Score,
+    ships::ShotsFired,
+    ufo::UfoSpawner,
+    waves::Wave,
 };
 use std::time::Duration;
 
 mod game;
+mod net;
 mod ui;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 enum GameState {
     #[default]
     MainMenu,
+    Login,
+    /// The player is picking an online game to join.
+    Lobby,
     InGame,
+    /// The player is entering their initials for a new high score.
+    HighScoreEntry,
+    GameOver,
+}
+
+/// Whether the game is paused. This is kept separate from [`GameState`] so that
+/// pausing doesn't run the `OnExit(GameState::InGame)` and
+/// `OnEnter(GameState::InGame)` systems, which would end the run.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+enum PauseState {
+    #[default]
+    Running,
+    Paused,
 }
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Cosmos Raiders".to_string(),
-                resolution: WindowResolution::new(700.0, 700.0),
-                ..default()
-            }),
-            ..default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "Cosmos Raiders".to_string(),
+                        resolution: WindowResolution::new(700.0, 700.0),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                // hot-reload assets, so formations can be edited while the game is running
+                .set(AssetPlugin {
+                    watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
+                    ..default()
+                }),
+        )
+        .add_asset::<Formations>()
+        .init_asset_loader::<FormationsLoader>()
+        .add_asset::<AlienKinds>()
+        .init_asset_loader::<AlienKindsLoader>()
+        .init_resource::<AlienKinds>()
         // .add_plugins(WorldInspectorPlugin::new())
-        .add_plugins((
-            AutomaticUpdate::<LowLevelAlien>::new()
-                .with_transform(TransformMode::GlobalTransform)
-                .with_spatial_ds(SpatialStructure::KDTree2),
-            AutomaticUpdate::<MidLevelAlien>::new()
+        .add_plugins(
+            AutomaticUpdate::<Alien>::new()
                 .with_transform(TransformMode::GlobalTransform)
                 .with_spatial_ds(SpatialStructure::KDTree2),
-            AutomaticUpdate::<HighLevelAlien>::new()
-                .with_transform(TransformMode::GlobalTransform)
-                .with_spatial_ds(SpatialStructure::KDTree2),
-        ))
+        )
         // background color
         .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
         .add_state::<GameState>()
+        .add_state::<PauseState>()
+        .init_resource::<ui::pause::PauseMenuPage>()
         .insert_resource(AlienMovement::default())
         .insert_resource(Score(0))
+        .insert_resource(load_high_scores())
+        .insert_resource(Lives::default())
         .insert_resource(load_collision_matrices())
         .insert_resource(AlienVelocity::default())
+        .insert_resource(AlienFiring::default())
+        .insert_resource(MarchBeat::default())
+        .add_event::<MarchStepEvent>()
+        .insert_resource(ShotsFired::default())
+        .insert_resource(UfoSpawner::default())
+        .insert_resource(Wave::default())
+        .insert_resource(ActiveFormation::default())
+        .add_event::<ShipHitEvent>()
+        .add_event::<game::pause::RestartEvent>()
+        .add_event::<net::NetEvent>()
+        .insert_resource(PositionSender::default())
         .add_systems(Startup, |mut commands: Commands| {
             commands.spawn(Camera2dBundle::default());
         })
         // main menu systems
-        .add_systems(OnEnter(GameState::MainMenu), ui::menu::setup_sys)
+        // the last run is cleaned up when a new one starts or the player goes back
+        // to the menu, so that it stays visible behind the game over screen
+        .add_systems(
+            OnEnter(GameState::MainMenu),
+            (
+                game::cleanup_sys,
+                game::multiplayer::leave_sys,
+                net::disconnect_sys,
+                ui::menu::setup_sys,
+            ),
+        )
         .add_systems(OnExit(GameState::MainMenu), ui::menu::remove_menu_sys)
         .add_systems(
             Update,
             ui::menu::handle_menu_interactions_sys.run_if(in_state(GameState::MainMenu)),
         )
+        // login systems
+        .add_systems(OnEnter(GameState::Login), ui::login::setup_sys)
+        .add_systems(OnExit(GameState::Login), ui::login::remove_login_sys)
+        .add_systems(
+            Update,
+            (
+                ui::login::typing_sys,
+                ui::login::handle_interactions_sys,
+                ui::login::update_sys
+                    .after(ui::login::typing_sys)
+                    .after(ui::login::handle_interactions_sys),
+            )
+                .run_if(in_state(GameState::Login)),
+        )
+        // lobby systems
+        .add_systems(OnEnter(GameState::Lobby), ui::lobby::setup_sys)
+        .add_systems(OnExit(GameState::Lobby), ui::lobby::remove_lobby_sys)
+        .add_systems(
+            Update,
+            (
+                ui::lobby::typing_sys,
+                ui::lobby::handle_interactions_sys,
+                ui::lobby::net_events_sys,
+                ui::lobby::show_sys
+                    .after(ui::lobby::typing_sys)
+                    .after(ui::lobby::handle_interactions_sys)
+                    .after(ui::lobby::net_events_sys),
+            )
+                .run_if(in_state(GameState::Lobby)),
+        )
         // game systems
-        .add_systems(OnEnter(GameState::InGame), game::setup_sys)
+        .add_systems(
+            OnEnter(GameState::InGame),
+            (game::cleanup_sys, game::setup_sys).chain(),
+        )
         .add_systems(
             Update,
             (
                 game::ships::PlayerShip::kbd_movement_sys,
                 game::ships::PlayerShip::gamepad_movement_sys,
+                game::scoreboard::update_sys,
+                game::highscores::update_sys,
+                game::scoreboard::score_popup_sys,
+                game::explosions::explosion_removal_sys,
+                game::waves::interstitial_sys,
+                game::waves::update_sys,
+                game::kinds::sync_sys,
+            )
+                .run_if(in_state(GameState::InGame))
+                .run_if(in_state(PauseState::Running)),
+        )
+        // single player systems, which online games leave to the server
+        .add_systems(
+            Update,
+            (
+                game::gameover::game_over_sys,
+                // spawning after respawn_sys stops a wave being skipped before its
+                // aliens exist
+                game::formations::spawn_formation_sys
+                    .after(game::aliens::respawn_sys)
+                    .after(game::kinds::sync_sys),
+                game::formations::hot_reload_sys,
+            )
+                .run_if(in_state(GameState::InGame))
+                .run_if(in_state(PauseState::Running))
+                .run_if(not(game::multiplayer::is_online)),
+        )
+        // everything below is held while the "WAVE N" banner is showing
+        .add_systems(
+            Update,
+            (
                 game::ships::PlayerShip::firing_sys,
                 game::ships::Laser::movement_sys,
                 game::aliens::movement_sys,
                 game::aliens::respawn_sys,
-                // alternate sprites every 1sec
-                game::aliens::sprite_alternator_sys.run_if(on_timer(Duration::from_secs_f32(0.5))),
-                game::aliens::LowLevelAlien::laser_collision_sys,
-                game::aliens::MidLevelAlien::laser_collision_sys,
-                game::aliens::HighLevelAlien::laser_collision_sys,
-                game::scoreboard::update_sys,
-                game::explosions::explosion_removal_sys,
-                game::gameover::game_over_sys,
+                game::aliens::speed_up_sys,
+                game::aliens::march_sys,
+                // alternate sprites on every step of the march
+                game::aliens::sprite_alternator_sys.after(game::aliens::march_sys),
+                game::aliens::laser_collision_sys,
+                game::shields::projectile_collision_sys::<game::ships::Laser>,
+                game::shields::alien_collision_sys,
+            )
+                .run_if(in_state(GameState::InGame))
+                .run_if(in_state(PauseState::Running))
+                .run_if(game::waves::wave_in_play)
+                .run_if(not(game::multiplayer::is_online)),
+        )
+        // pause systems
+        .add_systems(
+            Update,
+            game::pause::toggle_sys.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            Update,
+            (game::cleanup_sys, game::setup_sys)
+                .chain()
+                .run_if(on_event::<game::pause::RestartEvent>()),
+        )
+        .add_systems(
+            OnEnter(PauseState::Paused),
+            (game::pause::freeze_sys, ui::pause::setup_sys),
+        )
+        .add_systems(
+            OnExit(PauseState::Paused),
+            (game::pause::unfreeze_sys, ui::pause::remove_pause_sys),
+        )
+        .add_systems(
+            Update,
+            (
+                ui::pause::handle_interactions_sys,
+                ui::pause::show_sys.after(ui::pause::handle_interactions_sys),
+            )
+                .run_if(in_state(PauseState::Paused)),
+        )
+        // high score entry systems
+        .add_systems(OnEnter(GameState::HighScoreEntry), ui::highscore::setup_sys)
+        .add_systems(
+            OnExit(GameState::HighScoreEntry),
+            ui::highscore::remove_high_score_entry_sys,
+        )
+        .add_systems(
+            Update,
+            (
+                ui::highscore::input_sys,
+                ui::highscore::update_letters_sys.after(ui::highscore::input_sys),
+            )
+                .run_if(in_state(GameState::HighScoreEntry)),
+        )
+        // game over systems
+        .add_systems(OnEnter(GameState::GameOver), ui::gameover::setup_sys)
+        .add_systems(
+            OnExit(GameState::GameOver),
+            ui::gameover::remove_game_over_sys,
+        )
+        .add_systems(
+            Update,
+            ui::gameover::handle_interactions_sys.run_if(in_state(GameState::GameOver)),
+        )
+        // player lives systems
+        .add_systems(
+            Update,
+            (
+                game::ships::PlayerShip::hit_sys,
+                game::ships::ShipWreck::respawn_sys,
+                game::ships::Invulnerable::blink_sys,
+                game::lives::extra_life_sys.run_if(not(game::multiplayer::is_online)),
+                game::lives::update_sys,
+            )
+                .run_if(in_state(GameState::InGame))
+                .run_if(in_state(PauseState::Running)),
+        )
+        // online game systems
+        .add_systems(
+            Update,
+            (
+                game::multiplayer::send_position_sys,
+                game::multiplayer::shoot_sys,
+                game::multiplayer::remote_ships_sys,
+                game::multiplayer::server_sprites_sys,
+                game::multiplayer::server_events_sys,
+                game::multiplayer::game_over_sys,
+            )
+                .run_if(in_state(GameState::InGame))
+                .run_if(in_state(PauseState::Running))
+                .run_if(game::multiplayer::is_online),
+        )
+        // ufo systems
+        .add_systems(
+            Update,
+            (
+                game::ufo::spawn_sys,
+                game::ufo::movement_sys,
+                game::ufo::laser_collision_sys,
+            )
+                .run_if(in_state(GameState::InGame))
+                .run_if(in_state(PauseState::Running))
+                .run_if(game::waves::wave_in_play)
+                .run_if(not(game::multiplayer::is_online)),
+        )
+        // alien bomb systems
+        .add_systems(
+            Update,
+            (
+                game::aliens::firing_sys,
+                game::bombs::PlungerBomb::movement_sys,
+                game::bombs::SquigglyBomb::movement_sys,
+                game::bombs::PlungerBomb::ship_collision_sys,
+                game::bombs::SquigglyBomb::ship_collision_sys,
+                game::shields::projectile_collision_sys::<game::bombs::PlungerBomb>,
+                game::shields::projectile_collision_sys::<game::bombs::SquigglyBomb>,
             )
-                .run_if(in_state(GameState::InGame)),
+                .run_if(in_state(GameState::InGame))
+                .run_if(in_state(PauseState::Running))
+                .run_if(game::waves::wave_in_play)
+                .run_if(not(game::multiplayer::is_online)),
         )
         .add_plugins((
             TokioTasksPlugin::default(),
@@ -0,0 +1,344 @@
+// The client side of the Cosmos Raiders server's RPC protocol, which is shared
+// with the server through the `cosmos_raiders_protocol` crate.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_tokio_tasks::{
+    tokio::{
+        self,
+        sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    },
+    TaskContext, TokioTasksRuntime,
+};
+use cosmos_raiders_protocol::{
+    cr_server, Event as ServerEvent, GameEvent, GameID, GameInfo, GameMode, GamePage, GameQuery,
+    Period, RankedEntry, ScoreRange, ServerResult, MAX_PLAYERS, PROTOCOL_VERSION,
+};
+use hardlight::{rkyv::from_bytes, *};
+
+/// The address of the Cosmos Raiders server.
+pub const SERVER_ADDRESS: &str = "localhost:8080";
+/// How many games the lobby lists at a time.
+const LOBBY_PAGE_SIZE: u32 = 8;
+
+cr_server!();
+
+#[derive(Resource, Debug, Clone)]
+/// A global resource storing the account the player is logged in as. The token
+/// is used to resume the session on later connections to the server.
+pub struct Session {
+    pub name: String,
+    pub token: String,
+}
+
+/// Events pushed by the server to the topics the connection is subscribed to.
+type TopicEvents = UnboundedReceiver<(Topic, Vec<u8>)>;
+
+/// Connects to the server and checks that it speaks the same protocol as the
+/// game.
+pub async fn connect() -> Result<(CRServerClient, TopicEvents), String> {
+    let mut client = CRServerClient::new_self_signed(SERVER_ADDRESS, Compression::default());
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    client
+        .connect(events_tx)
+        .await
+        .map_err(|e| format!("Couldn't connect to the server: {e:?}"))?;
+    client
+        .handshake(PROTOCOL_VERSION)
+        .await
+        .map_err(|e| format!("Request failed: {e:?}"))?
+        .map_err(|e| e.to_string())?;
+    Ok((client, events_rx))
+}
+
+/// Connects to the server, optionally registers an account, then logs in.
+pub async fn login(name: String, password: String, register: bool) -> Result<Session, String> {
+    let (mut client, _) = connect().await?;
+
+    if register {
+        client
+            .register(name.clone(), password.clone())
+            .await
+            .map_err(|e| format!("Request failed: {e:?}"))?
+            .map_err(|e| e.to_string())?;
+    }
+    let token = client
+        .login(name.clone(), password)
+        .await
+        .map_err(|e| format!("Request failed: {e:?}"))?
+        .map_err(|e| e.to_string())?;
+
+    client.disconnect();
+    Ok(Session { name, token })
+}
+
+/// A request for the connection task to send to the server.
+#[derive(Debug)]
+pub enum NetCommand {
+    /// Lists the games that can be joined, starting after `cursor`.
+    ListGames {
+        cursor: Option<Vec<u8>>,
+    },
+    CreateGame {
+        private: bool,
+    },
+    JoinGame(GameID),
+    /// Joins a game by its join code.
+    JoinByCode(String),
+    LeaveGame,
+    StartGame,
+    UpdateX(f32),
+    Shoot,
+}
+
+/// Something that happened on the connection, sent to the game as a Bevy
+/// event.
+#[derive(Debug)]
+pub enum NetEvent {
+    /// The connection is set up and logged in.
+    Connected,
+    GamesListed(GamePage),
+    /// The player created or joined this game.
+    Joined(GameInfo),
+    Left,
+    Server(ServerEvent),
+    /// A request failed. The connection is still usable.
+    Failed(String),
+    /// The connection was lost or couldn't be set up.
+    Disconnected(String),
+}
+
+#[derive(Resource)]
+/// A global resource holding the connection to the server, while the player is
+/// playing online. Removing it closes the connection.
+pub struct NetClient {
+    commands: UnboundedSender<NetCommand>,
+}
+
+impl NetClient {
+    pub fn send(&self, command: NetCommand) {
+        // the connection task has already reported why it stopped
+        let _ = self.commands.send(command);
+    }
+}
+
+/// Opens a connection to the server for the logged in player, and inserts the
+/// [`NetClient`] resource to talk to it through. Everything the server sends
+/// back arrives as [`NetEvent`]s.
+pub fn start_connection(commands: &mut Commands, runtime: &TokioTasksRuntime, session: &Session) {
+    let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+    commands.insert_resource(NetClient {
+        commands: commands_tx,
+    });
+
+    let token = session.token.clone();
+    runtime.spawn_background_task(move |ctx| async move {
+        let result = run_connection(ctx.clone(), token, commands_rx).await;
+        let reason = match result {
+            Ok(()) => "Disconnected".to_string(),
+            Err(e) => e,
+        };
+        send_net_event(ctx, NetEvent::Disconnected(reason)).await;
+    });
+}
+
+/// Sends a [`NetEvent`] to the game from a background task.
+async fn send_net_event(mut ctx: TaskContext, ev: NetEvent) {
+    ctx.run_on_main_thread(move |ctx| {
+        ctx.world.send_event(ev);
+    })
+    .await;
+}
+
+/// Drops events that have already been passed on. The server sends each
+/// game's events in the order they're numbered, so an event numbered at or
+/// below the last one seen from its game is a repeat.
+#[derive(Default)]
+struct EventFilter {
+    last_seq: HashMap<GameID, u64>,
+}
+
+impl EventFilter {
+    /// Returns whether `ev` should be passed on.
+    fn accept(&mut self, ev: &GameEvent) -> bool {
+        let last = self.last_seq.entry(ev.game_id).or_default();
+        if ev.seq <= *last {
+            return false;
+        }
+        *last = ev.seq;
+        true
+    }
+}
+
+/// Runs the connection until the game drops the [`NetClient`], carrying out
+/// each [`NetCommand`] in turn.
+async fn run_connection(
+    ctx: TaskContext,
+    token: String,
+    mut commands: UnboundedReceiver<NetCommand>,
+) -> Result<(), String> {
+    let (mut client, mut events) = connect().await?;
+    client
+        .resume(token)
+        .await
+        .map_err(|e| format!("Request failed: {e:?}"))?
+        .map_err(|e| e.to_string())?;
+    send_net_event(ctx.clone(), NetEvent::Connected).await;
+
+    // forward the game's events while the connection waits on RPCs
+    let events_ctx = ctx.clone();
+    tokio::spawn(async move {
+        let mut filter = EventFilter::default();
+        while let Some((_topic, bytes)) = events.recv().await {
+            let ev = match from_bytes::<GameEvent>(&bytes) {
+                Ok(ev) => ev,
+                Err(e) => {
+                    warn!("Couldn't decode an event from the server: {e:?}");
+                    continue;
+                }
+            };
+            if !filter.accept(&ev) {
+                continue;
+            }
+            send_net_event(events_ctx.clone(), NetEvent::Server(ev.event)).await;
+        }
+    });
+
+    while let Some(command) = commands.recv().await {
+        let ev = match command {
+            NetCommand::ListGames { cursor } => {
+                let query = GameQuery {
+                    joinable_only: true,
+                    mode: None,
+                    cursor,
+                    count: LOBBY_PAGE_SIZE,
+                };
+                let res = client
+                    .list_games(query)
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?;
+                Some(match res {
+                    Ok(page) => NetEvent::GamesListed(page),
+                    Err(e) => NetEvent::Failed(e.to_string()),
+                })
+            }
+            NetCommand::CreateGame { private } => {
+                let res = client
+                    .create_game(MAX_PLAYERS, GameMode::Coop, private)
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?;
+                Some(match res {
+                    Ok(game) => NetEvent::Joined(game),
+                    Err(e) => NetEvent::Failed(e.to_string()),
+                })
+            }
+            NetCommand::JoinGame(game_id) => {
+                let res = client
+                    .join_game(game_id)
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?;
+                Some(match res {
+                    Ok(game) => NetEvent::Joined(game),
+                    Err(e) => NetEvent::Failed(e.to_string()),
+                })
+            }
+            NetCommand::JoinByCode(code) => {
+                let res = client
+                    .join_game_by_code(code)
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?;
+                Some(match res {
+                    Ok(game) => NetEvent::Joined(game),
+                    Err(e) => NetEvent::Failed(e.to_string()),
+                })
+            }
+            NetCommand::LeaveGame => {
+                let res = client
+                    .leave_game()
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?;
+                Some(match res {
+                    Ok(()) => NetEvent::Left,
+                    Err(e) => NetEvent::Failed(e.to_string()),
+                })
+            }
+            // the game starts for everyone once the server sends GameStarted
+            NetCommand::StartGame => {
+                let res = client
+                    .start_game()
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?;
+                res.err().map(|e| NetEvent::Failed(e.to_string()))
+            }
+            // position updates and shots are fire and forget, the server's
+            // snapshots show whether they landed
+            NetCommand::UpdateX(x) => {
+                client
+                    .update_x_position(x)
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?
+                    .ok();
+                None
+            }
+            NetCommand::Shoot => {
+                client
+                    .shoot()
+                    .await
+                    .map_err(|e| format!("Request failed: {e:?}"))?
+                    .ok();
+                None
+            }
+        };
+        if let Some(ev) = ev {
+            send_net_event(ctx.clone(), ev).await;
+        }
+    }
+
+    client.disconnect();
+    Ok(())
+}
+
+/// A system that closes the connection to the server, if there is one.
+pub fn disconnect_sys(mut commands: Commands) {
+    commands.remove_resource::<NetClient>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(game: u8, seq: u64) -> GameEvent {
+        GameEvent {
+            game_id: GameID([game; 16]),
+            seq,
+            timestamp: 0,
+            event: ServerEvent::GameStarted,
+        }
+    }
+
+    #[test]
+    fn repeated_and_stale_events_are_dropped() {
+        let mut filter = EventFilter::default();
+        assert!(filter.accept(&event(1, 1)));
+        assert!(filter.accept(&event(1, 2)));
+        assert!(!filter.accept(&event(1, 2)));
+        assert!(!filter.accept(&event(1, 1)));
+        assert!(filter.accept(&event(1, 3)));
+    }
+
+    #[test]
+    fn games_are_numbered_separately() {
+        let mut filter = EventFilter::default();
+        assert!(filter.accept(&event(1, 5)));
+        assert!(filter.accept(&event(2, 1)));
+        assert!(!filter.accept(&event(1, 4)));
+    }
+
+    #[test]
+    fn players_joining_part_way_through_see_later_events() {
+        let mut filter = EventFilter::default();
+        assert!(filter.accept(&event(1, 40)));
+        assert!(filter.accept(&event(1, 41)));
+    }
+}
@@ -0,0 +1,70 @@
+// ----- Classes shared between screens (they're really just callback functions
+// that modify bundles / text styles, but it's useful to think of them as .css
+// classes) -----
+use bevy::prelude::*;
+use bevy_ui_dsl::class_helpers::color::BLACK;
+
+pub fn c_root(b: &mut NodeBundle) {
+    b.style.width = Val::Percent(100.);
+    b.style.height = Val::Percent(100.)
+}
+
+pub fn c_black(b: &mut NodeBundle) {
+    b.background_color = BLACK.into();
+}
+
+pub fn text_box(_a: &AssetServer, b: &mut TextBundle) {
+    b.style.margin = UiRect::all(Val::Px(10.));
+}
+
+pub fn btn_c(_a: &AssetServer, b: &mut ButtonBundle) {
+    let s = &mut b.style;
+    s.width = Val::Px(128.);
+    s.height = Val::Px(24.);
+    s.justify_content = JustifyContent::Center;
+    s.align_items = AlignItems::Center;
+    b.background_color = Color::rgb_u8(66, 135, 245).into();
+}
+
+pub fn text_styling_c(assets: &AssetServer, s: &mut TextStyle) {
+    s.font = assets.load("fonts/space_invaders.ttf").into();
+    s.font_size = 16.;
+    s.color = Color::WHITE.into();
+}
+
+pub fn c_center(b: &mut NodeBundle) {
+    let s = &mut b.style;
+    s.flex_direction = FlexDirection::Column;
+    s.justify_content = JustifyContent::Center;
+    s.align_items = AlignItems::Center;
+}
+
+/// Covers the screen for a menu shown over a game.
+pub fn c_overlay(b: &mut NodeBundle) {
+    c_root(b);
+    b.style.justify_content = JustifyContent::Center;
+    b.style.align_items = AlignItems::Center;
+    // let the frozen game show through behind the panel
+    b.background_color = Color::rgba(0., 0., 0., 0.6).into();
+}
+
+/// The column of text and buttons in the middle of a [`c_overlay`].
+pub fn c_panel(b: &mut NodeBundle) {
+    let s = &mut b.style;
+    s.flex_direction = FlexDirection::Column;
+    s.align_items = AlignItems::Center;
+    s.padding = UiRect::all(Val::Px(10.));
+}
+
+/// A row of buttons under the rest of a screen.
+pub fn c_buttons(b: &mut NodeBundle) {
+    let s = &mut b.style;
+    s.flex_direction = FlexDirection::Row;
+    s.column_gap = Val::Px(10.);
+    s.margin = UiRect::top(Val::Px(10.));
+}
+
+pub fn c_title_text(assets: &AssetServer, s: &mut TextStyle) {
+    text_styling_c(assets, s);
+    s.font_size = 32.;
+}
@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use bevy_ui_dsl::*;
+
+use super::classes::{
+    btn_c, c_buttons, c_overlay, c_panel, c_title_text, text_box, text_styling_c,
+};
+use crate::{
+    game::{highscores::HighScores, scoreboard::Score, waves::Wave},
+    GameState,
+};
+
+#[derive(Component, Debug)]
+pub enum GameOverButtonId {
+    Retry,
+    MainMenu,
+}
+
+#[derive(Component, Debug)]
+pub struct GameOverMarker;
+
+pub fn setup_sys(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    high_scores: Res<HighScores>,
+) {
+    rooti(c_overlay, &assets, &mut commands, GameOverMarker, |p| {
+        node(c_panel, p, |p| {
+            text("GAME OVER", text_box, c_game_over_text, p);
+            text(format!("Score: {}", score.0), text_box, text_styling_c, p);
+            text(
+                format!("Wave: {}", wave.number),
+                text_box,
+                text_styling_c,
+                p,
+            );
+            text(
+                format!("Hi-Score: {}", high_scores.best()),
+                text_box,
+                text_styling_c,
+                p,
+            );
+            node(c_buttons, p, |p| {
+                text_buttoni("Retry", btn_c, text_styling_c, GameOverButtonId::Retry, p);
+                text_buttoni(
+                    "Main Menu",
+                    btn_c,
+                    text_styling_c,
+                    GameOverButtonId::MainMenu,
+                    p,
+                );
+            });
+        });
+    });
+}
+
+pub fn handle_interactions_sys(
+    ui_entities: Query<(&GameOverButtonId, &Interaction), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (id, inter) in &ui_entities {
+        match (id, inter) {
+            (GameOverButtonId::Retry, Interaction::Pressed) => {
+                next_state.set(GameState::InGame);
+            }
+            (GameOverButtonId::MainMenu, Interaction::Pressed) => {
+                next_state.set(GameState::MainMenu);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn remove_game_over_sys(
+    mut commands: Commands,
+    game_over_entities: Query<Entity, With<GameOverMarker>>,
+) {
+    for e in &game_over_entities {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+// ----- Classes -----
+fn c_game_over_text(assets: &AssetServer, s: &mut TextStyle) {
+    c_title_text(assets, s);
+    s.color = Color::RED;
+}
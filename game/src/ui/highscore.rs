@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use bevy_ui_dsl::*;
+
+use super::classes::{c_overlay, c_panel, c_title_text, text_box, text_styling_c};
+use crate::{
+    game::{
+        highscores::{HighScoreEntry, HighScores, INITIALS_LEN},
+        scoreboard::Score,
+        waves::Wave,
+    },
+    GameState,
+};
+
+const LETTER_COLOR: Color = Color::WHITE;
+const SELECTED_LETTER_COLOR: Color = Color::YELLOW;
+
+/// The initials being entered. Like the arcade original, each letter is picked
+/// by cycling through the alphabet, then confirmed to move on to the next one.
+#[derive(Resource, Debug)]
+pub struct InitialsEntry {
+    letters: [u8; INITIALS_LEN],
+    /// The index of the letter being picked.
+    cursor: usize,
+}
+
+impl Default for InitialsEntry {
+    fn default() -> Self {
+        Self {
+            letters: [b'A'; INITIALS_LEN],
+            cursor: 0,
+        }
+    }
+}
+
+impl InitialsEntry {
+    fn cycle(&mut self, by: i8) {
+        let letter = &mut self.letters[self.cursor];
+        *letter = b'A' + (*letter - b'A' + 26).wrapping_add_signed(by) % 26;
+    }
+
+    fn initials(&self) -> String {
+        self.letters.iter().map(|&l| l as char).collect()
+    }
+}
+
+/// One of the letters of the initials being entered.
+#[derive(Component, Debug)]
+pub struct InitialsLetter(usize);
+
+#[derive(Component, Debug)]
+pub struct HighScoreEntryMarker;
+
+pub fn setup_sys(mut commands: Commands, assets: Res<AssetServer>, score: Res<Score>) {
+    commands.insert_resource(InitialsEntry::default());
+
+    rooti(
+        c_overlay,
+        &assets,
+        &mut commands,
+        HighScoreEntryMarker,
+        |p| {
+            node(c_panel, p, |p| {
+                text("NEW HIGH SCORE", text_box, c_high_score_text, p);
+                text(format!("Score: {}", score.0), text_box, text_styling_c, p);
+                node(c_letters, p, |p| {
+                    for i in 0..INITIALS_LEN {
+                        texti("A", text_box, c_letter_text, InitialsLetter(i), p);
+                    }
+                });
+                text(
+                    "Up/Down to pick, Enter to confirm",
+                    text_box,
+                    text_styling_c,
+                    p,
+                );
+            });
+        },
+    );
+}
+
+/// A system that lets the player pick their initials with the keyboard or a
+/// gamepad, then saves the new high score.
+pub fn input_sys(
+    keyboard_input: Res<Input<KeyCode>>,
+    button_inputs: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    mut entry: ResMut<InitialsEntry>,
+    mut high_scores: ResMut<HighScores>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let gamepad_pressed = |button| {
+        gamepads
+            .iter()
+            .any(|gp| button_inputs.just_pressed(GamepadButton::new(gp, button)))
+    };
+
+    if keyboard_input.just_pressed(KeyCode::Up) || gamepad_pressed(GamepadButtonType::DPadUp) {
+        entry.cycle(1);
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) || gamepad_pressed(GamepadButtonType::DPadDown) {
+        entry.cycle(-1);
+    }
+    if (keyboard_input.just_pressed(KeyCode::Back)
+        || keyboard_input.just_pressed(KeyCode::Left)
+        || gamepad_pressed(GamepadButtonType::East))
+        && entry.cursor > 0
+    {
+        entry.cursor -= 1;
+    }
+
+    let confirmed = keyboard_input.just_pressed(KeyCode::Return)
+        || keyboard_input.just_pressed(KeyCode::Space)
+        || keyboard_input.just_pressed(KeyCode::Right)
+        || gamepad_pressed(GamepadButtonType::South);
+    if !confirmed {
+        return;
+    }
+    if entry.cursor + 1 < INITIALS_LEN {
+        entry.cursor += 1;
+        return;
+    }
+
+    high_scores.insert(HighScoreEntry::new(entry.initials(), score.0, wave.number));
+    if let Err(e) = high_scores.save() {
+        warn!("Couldn't save high scores: {e}");
+    }
+    next_state.set(GameState::GameOver);
+}
+
+pub fn update_letters_sys(
+    entry: Res<InitialsEntry>,
+    mut letters: Query<(&InitialsLetter, &mut Text)>,
+) {
+    if !entry.is_changed() {
+        return;
+    }
+    for (letter, mut text) in letters.iter_mut() {
+        let section = &mut text.sections[0];
+        section.value = (entry.letters[letter.0] as char).to_string();
+        section.style.color = if letter.0 == entry.cursor {
+            SELECTED_LETTER_COLOR
+        } else {
+            LETTER_COLOR
+        };
+    }
+}
+
+pub fn remove_high_score_entry_sys(
+    mut commands: Commands,
+    entities: Query<Entity, With<HighScoreEntryMarker>>,
+) {
+    for e in &entities {
+        commands.entity(e).despawn_recursive();
+    }
+    commands.remove_resource::<InitialsEntry>();
+}
+
+// ----- Classes -----
+fn c_letters(b: &mut NodeBundle) {
+    let s = &mut b.style;
+    s.flex_direction = FlexDirection::Row;
+    s.column_gap = Val::Px(10.);
+}
+
+fn c_high_score_text(assets: &AssetServer, s: &mut TextStyle) {
+    c_title_text(assets, s);
+    s.color = Color::YELLOW;
+}
+
+fn c_letter_text(assets: &AssetServer, s: &mut TextStyle) {
+    text_styling_c(assets, s);
+    s.font_size = 48.;
+}
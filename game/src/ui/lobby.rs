@@ -0,0 +1,365 @@
+use bevy::prelude::*;
+use bevy_tokio_tasks::TokioTasksRuntime;
+use bevy_ui_dsl::*;
+use cosmos_raiders_protocol::{Event as ServerEvent, GameID, GameInfo, GameMode, GameSummary};
+
+use super::classes::{
+    btn_c, c_black, c_buttons, c_center, c_root, c_title_text, text_box, text_styling_c,
+};
+use crate::{
+    game::multiplayer::OnlineGame,
+    net::{self, NetClient, NetCommand, NetEvent, Session},
+    GameState,
+};
+
+/// How many letters and digits are in a join code, not counting the dash.
+const JOIN_CODE_LEN: usize = 6;
+
+#[derive(Component, Debug)]
+pub enum LobbyButtonId {
+    Refresh,
+    NextPage,
+    Create,
+    CreatePrivate,
+    JoinByCode,
+    Back,
+    Start,
+    Leave,
+}
+
+/// A button that joins one of the listed games.
+#[derive(Component, Debug)]
+pub struct JoinGameButton(GameID);
+
+#[derive(Component, Debug)]
+pub struct LobbyMarker;
+
+/// What the lobby is showing. The screen is rebuilt whenever this changes.
+#[derive(Resource, Debug, Default)]
+pub struct LobbyView {
+    status: String,
+    games: Vec<GameSummary>,
+    /// The cursor for the next page of games, if there is one.
+    next_page: Option<Vec<u8>>,
+    /// The game the player is waiting in for the host to start.
+    joined: Option<GameInfo>,
+    /// The join code typed in so far.
+    code: String,
+}
+
+pub fn setup_sys(
+    mut commands: Commands,
+    runtime: Res<TokioTasksRuntime>,
+    session: Res<Session>,
+    client: Option<Res<NetClient>>,
+) {
+    let status = match client {
+        // back in the lobby after an online game
+        Some(client) => {
+            client.send(NetCommand::ListGames { cursor: None });
+            "Finding games..."
+        }
+        None => {
+            net::start_connection(&mut commands, &runtime, &session);
+            "Connecting..."
+        }
+    };
+    commands.insert_resource(LobbyView {
+        status: status.to_string(),
+        ..default()
+    });
+}
+
+/// A system that (re)builds the lobby screen.
+pub fn show_sys(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    view: Res<LobbyView>,
+    session: Res<Session>,
+    lobby_entities: Query<Entity, With<LobbyMarker>>,
+) {
+    if !view.is_changed() {
+        return;
+    }
+    for e in &lobby_entities {
+        commands.entity(e).despawn_recursive();
+    }
+
+    rooti(
+        (c_root, c_black, c_center),
+        &assets,
+        &mut commands,
+        LobbyMarker,
+        |p| {
+            text("MULTIPLAYER", text_box, c_title_text, p);
+            text(&view.status, text_box, text_styling_c, p);
+            if let Some(game) = &view.joined {
+                text(
+                    format!("Join code: {}", game.code),
+                    text_box,
+                    text_styling_c,
+                    p,
+                );
+                node(c_list, p, |p| {
+                    for player in &game.players {
+                        let label = if *player == game.host {
+                            format!("{player} (host)")
+                        } else {
+                            player.clone()
+                        };
+                        text(label, text_box, text_styling_c, p);
+                    }
+                });
+                node(c_buttons, p, |p| {
+                    if game.host == session.name {
+                        text_buttoni("Start", btn_c, text_styling_c, LobbyButtonId::Start, p);
+                    }
+                    text_buttoni("Leave", btn_c, text_styling_c, LobbyButtonId::Leave, p);
+                });
+                return;
+            }
+            node(c_list, p, |p| {
+                for game in &view.games {
+                    text_buttoni(
+                        summary_label(game),
+                        c_game_btn,
+                        text_styling_c,
+                        JoinGameButton(game.id),
+                        p,
+                    );
+                }
+                if view.next_page.is_some() {
+                    text_buttoni(
+                        "More Games",
+                        btn_c,
+                        text_styling_c,
+                        LobbyButtonId::NextPage,
+                        p,
+                    );
+                }
+            });
+            node(c_buttons, p, |p| {
+                text_buttoni("Refresh", btn_c, text_styling_c, LobbyButtonId::Refresh, p);
+                text_buttoni(
+                    "Create Game",
+                    btn_c,
+                    text_styling_c,
+                    LobbyButtonId::Create,
+                    p,
+                );
+                text_buttoni(
+                    "Create Private",
+                    btn_c,
+                    text_styling_c,
+                    LobbyButtonId::CreatePrivate,
+                    p,
+                );
+                text_buttoni("Back", btn_c, text_styling_c, LobbyButtonId::Back, p);
+            });
+            node(c_buttons, p, |p| {
+                let code = if view.code.is_empty() {
+                    "Type a code".to_string()
+                } else {
+                    view.code.clone()
+                };
+                text(code, c_code_text, text_styling_c, p);
+                text_buttoni(
+                    "Join Code",
+                    btn_c,
+                    text_styling_c,
+                    LobbyButtonId::JoinByCode,
+                    p,
+                );
+            });
+        },
+    );
+}
+
+pub fn handle_interactions_sys(
+    ui_entities: Query<(&LobbyButtonId, &Interaction), Changed<Interaction>>,
+    join_buttons: Query<(&JoinGameButton, &Interaction), Changed<Interaction>>,
+    client: Option<Res<NetClient>>,
+    view: Res<LobbyView>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (id, inter) in &ui_entities {
+        match (id, inter, &client) {
+            (LobbyButtonId::Refresh, Interaction::Pressed, Some(client)) => {
+                client.send(NetCommand::ListGames { cursor: None })
+            }
+            (LobbyButtonId::NextPage, Interaction::Pressed, Some(client)) => {
+                client.send(NetCommand::ListGames {
+                    cursor: view.next_page.clone(),
+                })
+            }
+            (LobbyButtonId::Create, Interaction::Pressed, Some(client)) => {
+                client.send(NetCommand::CreateGame { private: false })
+            }
+            (LobbyButtonId::CreatePrivate, Interaction::Pressed, Some(client)) => {
+                client.send(NetCommand::CreateGame { private: true })
+            }
+            (LobbyButtonId::JoinByCode, Interaction::Pressed, Some(client)) => {
+                client.send(NetCommand::JoinByCode(view.code.clone()))
+            }
+            (LobbyButtonId::Start, Interaction::Pressed, Some(client)) => {
+                client.send(NetCommand::StartGame)
+            }
+            (LobbyButtonId::Leave, Interaction::Pressed, Some(client)) => {
+                client.send(NetCommand::LeaveGame)
+            }
+            (LobbyButtonId::Back, Interaction::Pressed, _) => next_state.set(GameState::MainMenu),
+            _ => {}
+        }
+    }
+    for (button, inter) in &join_buttons {
+        if let (Interaction::Pressed, Some(client)) = (inter, &client) {
+            client.send(NetCommand::JoinGame(button.0));
+        }
+    }
+}
+
+/// A system that types a join code. Enter joins the game.
+pub fn typing_sys(
+    mut chars: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut view: ResMut<LobbyView>,
+    client: Option<Res<NetClient>>,
+) {
+    if view.joined.is_some() {
+        chars.clear();
+        return;
+    }
+    for ev in chars.iter() {
+        // the server ignores the dash in the middle, so it isn't typed
+        if ev.char.is_ascii_alphanumeric() && view.code.len() < JOIN_CODE_LEN {
+            view.code.push(ev.char.to_ascii_uppercase());
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        view.code.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        if let Some(client) = &client {
+            client.send(NetCommand::JoinByCode(view.code.clone()));
+        }
+    }
+}
+
+/// A system that shows the server's replies, and starts the game once the
+/// player is in one.
+pub fn net_events_sys(
+    mut commands: Commands,
+    mut net_events: EventReader<NetEvent>,
+    client: Option<Res<NetClient>>,
+    mut view: ResMut<LobbyView>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for ev in net_events.iter() {
+        match ev {
+            NetEvent::Connected => {
+                if let Some(client) = &client {
+                    client.send(NetCommand::ListGames { cursor: None });
+                }
+                view.status = "Finding games...".to_string();
+            }
+            NetEvent::GamesListed(page) => {
+                view.status = if page.games.is_empty() {
+                    "No games to join, create one!".to_string()
+                } else {
+                    "Pick a game to join".to_string()
+                };
+                view.games = page.games.clone();
+                view.next_page = page.next.clone();
+            }
+            NetEvent::Joined(game) => {
+                view.code.clear();
+                view.status = format!("Waiting in {}", game_label(&game.id));
+                view.joined = Some(game.clone());
+            }
+            NetEvent::Left => {
+                view.joined = None;
+                if let Some(client) = &client {
+                    client.send(NetCommand::ListGames { cursor: None });
+                }
+                view.status = "Finding games...".to_string();
+            }
+            NetEvent::Server(ServerEvent::PlayerJoined { name }) => {
+                if let Some(game) = &mut view.joined {
+                    if !game.players.contains(name) {
+                        game.players.push(name.clone());
+                    }
+                }
+            }
+            NetEvent::Server(ServerEvent::PlayerLeft { name }) => {
+                if let Some(game) = &mut view.joined {
+                    game.players.retain(|player| player != name);
+                    // mirrors the server handing the game to the next player
+                    if game.host == *name {
+                        if let Some(next) = game.players.first() {
+                            game.host = next.clone();
+                        }
+                    }
+                }
+            }
+            NetEvent::Server(ServerEvent::GameStarted) => {
+                if let Some(game) = &view.joined {
+                    commands.insert_resource(OnlineGame { game_id: game.id });
+                    next_state.set(GameState::InGame);
+                }
+            }
+            NetEvent::Failed(e) => view.status = e.clone(),
+            NetEvent::Disconnected(reason) => {
+                commands.remove_resource::<NetClient>();
+                view.status = reason.clone();
+                view.games.clear();
+                view.next_page = None;
+                view.joined = None;
+            }
+            NetEvent::Server(_) => {}
+        }
+    }
+}
+
+/// A lobby listing for a game, e.g. `alice Coop 1/4`.
+fn summary_label(game: &GameSummary) -> String {
+    let mode = match game.mode {
+        GameMode::Coop => "Coop",
+        GameMode::Versus => "Versus",
+    };
+    format!(
+        "{} {mode} {}/{}",
+        game.host, game.player_count, game.capacity
+    )
+}
+
+/// A short name for a game, made from the start of its ID.
+fn game_label(game_id: &GameID) -> String {
+    let hex: String = game_id.0[..3].iter().map(|b| format!("{b:02x}")).collect();
+    format!("Game {hex}")
+}
+
+pub fn remove_lobby_sys(mut commands: Commands, lobby_entities: Query<Entity, With<LobbyMarker>>) {
+    for e in &lobby_entities {
+        commands.entity(e).despawn_recursive();
+    }
+    commands.remove_resource::<LobbyView>();
+}
+
+// ----- Classes -----
+fn c_list(b: &mut NodeBundle) {
+    let s = &mut b.style;
+    s.flex_direction = FlexDirection::Column;
+    s.align_items = AlignItems::Center;
+    s.row_gap = Val::Px(6.);
+    s.min_height = Val::Px(100.);
+}
+
+fn c_game_btn(assets: &AssetServer, b: &mut ButtonBundle) {
+    btn_c(assets, b);
+    b.style.width = Val::Px(260.);
+}
+
+fn c_code_text(_a: &AssetServer, b: &mut TextBundle) {
+    b.style.width = Val::Px(128.);
+    b.style.margin = UiRect::all(Val::Px(4.));
+}
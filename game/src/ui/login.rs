@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+use bevy_tokio_tasks::TokioTasksRuntime;
+use bevy_ui_dsl::*;
+
+use super::classes::{
+    btn_c, c_black, c_buttons, c_center, c_root, c_title_text, text_box, text_styling_c,
+};
+use crate::{net, GameState};
+
+const FIELD_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const FOCUSED_FIELD_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+const MAX_FIELD_LEN: usize = 32;
+
+#[derive(Component, Debug)]
+pub enum LoginButtonId {
+    Login,
+    Register,
+    Back,
+}
+
+/// The text fields on the login screen.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoginField {
+    #[default]
+    Name,
+    Password,
+}
+
+#[derive(Component, Debug)]
+pub struct LoginMarker;
+
+/// marker component for the text showing the result of the last attempt
+#[derive(Component, Debug)]
+pub struct LoginStatus;
+
+#[derive(Resource, Debug, Default)]
+/// A resource storing what has been typed into the login screen.
+pub struct LoginForm {
+    name: String,
+    password: String,
+    focused: LoginField,
+    /// Whether a login request is waiting for the server.
+    pending: bool,
+    status: String,
+}
+
+pub fn setup_sys(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(LoginForm::default());
+
+    rooti(
+        (c_root, c_black, c_center),
+        &assets,
+        &mut commands,
+        LoginMarker,
+        |p| {
+            text("LOG IN", text_box, c_title_text, p);
+            text("Name", text_box, text_styling_c, p);
+            buttoni(c_field, LoginField::Name, p, |p| {
+                text("", c_field_text, text_styling_c, p);
+            });
+            text("Password", text_box, text_styling_c, p);
+            buttoni(c_field, LoginField::Password, p, |p| {
+                text("", c_field_text, text_styling_c, p);
+            });
+            node(c_buttons, p, |p| {
+                text_buttoni("Login", btn_c, text_styling_c, LoginButtonId::Login, p);
+                text_buttoni(
+                    "Register",
+                    btn_c,
+                    text_styling_c,
+                    LoginButtonId::Register,
+                    p,
+                );
+                text_buttoni("Back", btn_c, text_styling_c, LoginButtonId::Back, p);
+            });
+            texti("", text_box, text_styling_c, LoginStatus, p);
+        },
+    );
+}
+
+/// A system that types into the focused field. Tab switches fields and Enter
+/// logs in.
+pub fn typing_sys(
+    mut chars: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut form: ResMut<LoginForm>,
+    runtime: Res<TokioTasksRuntime>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        form.focused = match form.focused {
+            LoginField::Name => LoginField::Password,
+            LoginField::Password => LoginField::Name,
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        submit(&mut form, &runtime, false);
+    }
+
+    for ev in chars.iter() {
+        if ev.char.is_control() {
+            continue;
+        }
+        let field = form.field_mut();
+        if field.len() < MAX_FIELD_LEN {
+            field.push(ev.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        form.field_mut().pop();
+    }
+}
+
+impl LoginForm {
+    fn field_mut(&mut self) -> &mut String {
+        match self.focused {
+            LoginField::Name => &mut self.name,
+            LoginField::Password => &mut self.password,
+        }
+    }
+}
+
+/// Sends the form to the server in the background. The result is applied on the
+/// main thread once the server replies.
+fn submit(form: &mut LoginForm, runtime: &TokioTasksRuntime, register: bool) {
+    if form.pending {
+        return;
+    }
+    form.pending = true;
+    form.status = "Connecting...".to_string();
+
+    let name = form.name.clone();
+    let password = form.password.clone();
+    runtime.spawn_background_task(move |mut ctx| async move {
+        let result = net::login(name, password, register).await;
+        ctx.run_on_main_thread(move |ctx| {
+            let world = ctx.world;
+            let Some(mut form) = world.get_resource_mut::<LoginForm>() else {
+                // the player left the login screen before the server replied
+                return;
+            };
+            form.pending = false;
+            let session = match result {
+                Ok(session) => session,
+                Err(e) => {
+                    form.status = e;
+                    return;
+                }
+            };
+            info!("Logged in as {}", session.name);
+            world.insert_resource(session);
+            world
+                .resource_mut::<NextState<GameState>>()
+                .set(GameState::MainMenu);
+        })
+        .await;
+    });
+}
+
+pub fn handle_interactions_sys(
+    ui_entities: Query<(&LoginButtonId, &Interaction), Changed<Interaction>>,
+    fields: Query<(&LoginField, &Interaction), Changed<Interaction>>,
+    mut form: ResMut<LoginForm>,
+    runtime: Res<TokioTasksRuntime>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (field, inter) in &fields {
+        if *inter == Interaction::Pressed {
+            form.focused = *field;
+        }
+    }
+    for (id, inter) in &ui_entities {
+        match (id, inter) {
+            (LoginButtonId::Login, Interaction::Pressed) => submit(&mut form, &runtime, false),
+            (LoginButtonId::Register, Interaction::Pressed) => submit(&mut form, &runtime, true),
+            (LoginButtonId::Back, Interaction::Pressed) => next_state.set(GameState::MainMenu),
+            _ => {}
+        }
+    }
+}
+
+/// A system that shows what has been typed, with the password hidden.
+pub fn update_sys(
+    form: Res<LoginForm>,
+    mut fields: Query<(&LoginField, &mut BackgroundColor, &Children)>,
+    mut texts: Query<&mut Text>,
+    status: Query<Entity, With<LoginStatus>>,
+) {
+    if !form.is_changed() {
+        return;
+    }
+    for (field, mut color, children) in fields.iter_mut() {
+        *color = if *field == form.focused {
+            FOCUSED_FIELD_COLOR
+        } else {
+            FIELD_COLOR
+        }
+        .into();
+        let value = match field {
+            LoginField::Name => form.name.clone(),
+            LoginField::Password => "*".repeat(form.password.len()),
+        };
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = value.clone();
+            }
+        }
+    }
+    for entity in &status {
+        if let Ok(mut text) = texts.get_mut(entity) {
+            text.sections[0].value = form.status.clone();
+        }
+    }
+}
+
+pub fn remove_login_sys(mut commands: Commands, login_entities: Query<Entity, With<LoginMarker>>) {
+    for e in &login_entities {
+        commands.entity(e).despawn_recursive();
+    }
+    commands.remove_resource::<LoginForm>();
+}
+
+// ----- Classes -----
+fn c_field(_a: &AssetServer, b: &mut ButtonBundle) {
+    let s = &mut b.style;
+    s.width = Val::Px(200.);
+    s.height = Val::Px(24.);
+    s.align_items = AlignItems::Center;
+    s.padding = UiRect::horizontal(Val::Px(4.));
+    b.background_color = FIELD_COLOR.into();
+}
+
+fn c_field_text(_a: &AssetServer, b: &mut TextBundle) {
+    b.style.margin = UiRect::all(Val::Px(2.));
+}
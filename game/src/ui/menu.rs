@@ -1,7 +1,8 @@
 use bevy::prelude::*;
-use bevy_ui_dsl::{class_helpers::color::BLACK, *};
+use bevy_ui_dsl::*;
 
-use crate::GameState;
+use super::classes::{btn_c, c_black, c_root, text_box, text_styling_c};
+use crate::{net::Session, GameState};
 
 #[derive(Component, Debug)]
 pub enum MainMenuButtonId {
@@ -16,6 +17,7 @@ pub fn setup_sys(
     assets: Res<AssetServer>,
     mut scale: ResMut<UiScale>,
     mut next_state: ResMut<NextState<GameState>>,
+    session: Option<Res<Session>>,
 ) {
     // if "--skip-menu" is passed as a command line argument, skip the menu
     if std::env::args().any(|s| s == "--skip-menu") {
@@ -29,14 +31,26 @@ pub fn setup_sys(
         node((c_half, c_black), p, |p| {
             text_buttoni(
                 "Single Player",
-                left_btn_c,
+                btn_c,
                 text_styling_c,
                 MainMenuButtonId::SinglePlayer,
                 p,
             );
+            text_buttoni(
+                "Multiplayer",
+                btn_c,
+                text_styling_c,
+                MainMenuButtonId::Multiplayer,
+                p,
+            );
+            text_buttoni("Login", btn_c, text_styling_c, MainMenuButtonId::Login, p);
         });
         node((c_half, c_blue), p, |p| {
-            text("This is the right pane!", text_box, text_styling_c, p);
+            let status = match &session {
+                Some(session) => format!("Logged in as {}", session.name),
+                None => "Not logged in".to_string(),
+            };
+            text(status, text_box, text_styling_c, p);
         });
     });
 }
@@ -47,6 +61,7 @@ pub struct MenuMarker;
 pub fn handle_menu_interactions_sys(
     ui_entities: Query<(&MainMenuButtonId, &Interaction), Changed<Interaction>>,
     mut next_state: ResMut<NextState<GameState>>,
+    session: Option<Res<Session>>,
 ) {
     for (id, inter) in &ui_entities {
         match (id, inter) {
@@ -54,6 +69,14 @@ pub fn handle_menu_interactions_sys(
                 println!("Single player button pressed!!!");
                 next_state.set(GameState::InGame);
             }
+            // online games need an account, so log in first
+            (MainMenuButtonId::Multiplayer, Interaction::Pressed) => match &session {
+                Some(_) => next_state.set(GameState::Lobby),
+                None => next_state.set(GameState::Login),
+            },
+            (MainMenuButtonId::Login, Interaction::Pressed) => {
+                next_state.set(GameState::Login);
+            }
             _ => {}
         }
     }
@@ -67,11 +90,6 @@ pub fn remove_menu_sys(mut commands: Commands, menu_entities: Query<Entity, With
 
 // ----- Classes (they're really just callback functions that modify bundles /
 // text styles, but it's useful to think of them as .css classes) -----
-fn c_root(b: &mut NodeBundle) {
-    b.style.width = Val::Percent(100.);
-    b.style.height = Val::Percent(100.)
-}
-
 fn c_half(b: &mut NodeBundle) {
     let s = &mut b.style;
     s.width = Val::Percent(50.);
@@ -79,32 +97,10 @@ fn c_half(b: &mut NodeBundle) {
     s.flex_direction = FlexDirection::Column;
     s.justify_content = JustifyContent::Center;
     s.align_items = AlignItems::Center;
+    s.row_gap = Val::Px(10.);
     s.padding = UiRect::all(Val::Px(10.));
 }
 
-fn c_black(b: &mut NodeBundle) {
-    b.background_color = BLACK.into();
-}
-
 fn c_blue(b: &mut NodeBundle) {
     b.background_color = Color::rgb_u8(125, 164, 212).into();
 }
-
-fn text_box(_a: &AssetServer, b: &mut TextBundle) {
-    b.style.margin = UiRect::all(Val::Px(10.));
-}
-
-fn left_btn_c(_a: &AssetServer, b: &mut ButtonBundle) {
-    let s = &mut b.style;
-    s.width = Val::Px(128.);
-    s.height = Val::Px(24.);
-    s.justify_content = JustifyContent::Center;
-    s.align_items = AlignItems::Center;
-    b.background_color = Color::rgb_u8(66, 135, 245).into();
-}
-
-fn text_styling_c(assets: &AssetServer, s: &mut TextStyle) {
-    s.font = assets.load("fonts/space_invaders.ttf").into();
-    s.font_size = 16.;
-    s.color = Color::WHITE.into();
-}
@@ -1 +1,7 @@
+pub mod classes;
+pub mod gameover;
+pub mod highscore;
+pub mod lobby;
+pub mod login;
 pub mod menu;
+pub mod pause;
@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use bevy_ui_dsl::*;
+
+use super::classes::{btn_c, c_overlay, c_panel, c_title_text, text_box, text_styling_c};
+use crate::{game::pause::RestartEvent, GameState, PauseState};
+
+#[derive(Component, Debug)]
+pub enum PauseButtonId {
+    Resume,
+    Restart,
+    Settings,
+    QuitToMenu,
+    ToggleSound,
+    Back,
+}
+
+#[derive(Component, Debug)]
+pub struct PauseMarker;
+
+/// Which page of the pause overlay is showing. The overlay is rebuilt whenever
+/// this changes.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMenuPage {
+    #[default]
+    Main,
+    Settings,
+}
+
+pub fn setup_sys(mut page: ResMut<PauseMenuPage>) {
+    // always assigned, so the overlay is shown even if the page didn't change
+    *page = PauseMenuPage::Main;
+}
+
+/// A system that (re)builds the pause overlay for the current page.
+pub fn show_sys(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    page: Res<PauseMenuPage>,
+    volume: Res<GlobalVolume>,
+    pause_entities: Query<Entity, With<PauseMarker>>,
+) {
+    if !page.is_changed() {
+        return;
+    }
+    for e in &pause_entities {
+        commands.entity(e).despawn_recursive();
+    }
+
+    rooti(c_overlay, &assets, &mut commands, PauseMarker, |p| {
+        node(c_menu_panel, p, |p| match *page {
+            PauseMenuPage::Main => {
+                text("PAUSED", text_box, c_title_text, p);
+                text_buttoni("Resume", btn_c, text_styling_c, PauseButtonId::Resume, p);
+                text_buttoni("Restart", btn_c, text_styling_c, PauseButtonId::Restart, p);
+                text_buttoni(
+                    "Settings",
+                    btn_c,
+                    text_styling_c,
+                    PauseButtonId::Settings,
+                    p,
+                );
+                text_buttoni(
+                    "Quit to Menu",
+                    btn_c,
+                    text_styling_c,
+                    PauseButtonId::QuitToMenu,
+                    p,
+                );
+            }
+            PauseMenuPage::Settings => {
+                text("SETTINGS", text_box, c_title_text, p);
+                let sound = if volume.volume.get() > 0.0 {
+                    "On"
+                } else {
+                    "Off"
+                };
+                text_buttoni(
+                    format!("Sound: {sound}"),
+                    btn_c,
+                    text_styling_c,
+                    PauseButtonId::ToggleSound,
+                    p,
+                );
+                text_buttoni("Back", btn_c, text_styling_c, PauseButtonId::Back, p);
+            }
+        });
+    });
+}
+
+pub fn handle_interactions_sys(
+    ui_entities: Query<(&PauseButtonId, &Interaction), Changed<Interaction>>,
+    mut page: ResMut<PauseMenuPage>,
+    mut volume: ResMut<GlobalVolume>,
+    sinks: Query<&AudioSink>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+    mut restarts: EventWriter<RestartEvent>,
+) {
+    for (id, inter) in &ui_entities {
+        if *inter != Interaction::Pressed {
+            continue;
+        }
+        match id {
+            PauseButtonId::Resume => next_pause_state.set(PauseState::Running),
+            PauseButtonId::Restart => {
+                restarts.send(RestartEvent);
+                next_pause_state.set(PauseState::Running);
+            }
+            PauseButtonId::Settings => *page = PauseMenuPage::Settings,
+            PauseButtonId::QuitToMenu => {
+                next_game_state.set(GameState::MainMenu);
+                next_pause_state.set(PauseState::Running);
+            }
+            PauseButtonId::ToggleSound => {
+                let level = if volume.volume.get() > 0.0 { 0.0 } else { 1.0 };
+                *volume = GlobalVolume::new(level);
+                // the global volume only applies to sounds started from now on
+                for sink in &sinks {
+                    sink.set_volume(level);
+                }
+                page.set_changed();
+            }
+            PauseButtonId::Back => *page = PauseMenuPage::Main,
+        }
+    }
+}
+
+pub fn remove_pause_sys(mut commands: Commands, pause_entities: Query<Entity, With<PauseMarker>>) {
+    for e in &pause_entities {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+// ----- Classes -----
+fn c_menu_panel(b: &mut NodeBundle) {
+    c_panel(b);
+    b.style.row_gap = Val::Px(10.);
+}
@@ -0,0 +1,493 @@
+// The RPC protocol spoken between the game and the Cosmos Raiders server.
+//
+// hardlight generates the client and the server's `Handler` in whichever crate
+// the `#[rpc]` trait is declared, and the server has to implement the trait on
+// that `Handler`. So the trait is declared by the `cr_server!` macro, which the
+// game and the server both expand, while the types it uses live here.
+
+use cosmos_raiders_sim::simulation::{SimEvent, Simulation};
+use hardlight::*;
+
+/// The version of the protocol. Bump this whenever the trait or any of the
+/// types below change, so that out of date clients are turned away by
+/// `handshake` instead of failing in confusing ways.
+pub const PROTOCOL_VERSION: u32 = 9;
+
+/// The most entries that can be fetched by a single `top_scores` call.
+pub const MAX_SCORE_RANGE: u32 = 100;
+/// The most players a game can be created for.
+pub const MAX_PLAYERS: u32 = 4;
+/// The most games that can be fetched by a single `list_games` call.
+pub const MAX_GAMES_PAGE: u32 = 50;
+
+/// Declares the `CRServer` RPC trait and its connection `State`, generating
+/// `CRServerClient` and `Handler` in the calling module. hardlight identifies
+/// RPCs by their position in the trait, which is why it's only written once.
+///
+/// The calling module must have `hardlight::*` in scope, along with the
+/// protocol types used in the trait.
+#[macro_export]
+macro_rules! cr_server {
+    () => {
+        #[rpc]
+        pub trait CRServer {
+            /// Checks that the client speaks the same protocol as the server.
+            /// Must be called before logging in.
+            async fn handshake(&self, version: u32) -> HandlerResult<ServerResult<()>>;
+            async fn register(
+                &self,
+                name: String,
+                password: String,
+            ) -> HandlerResult<ServerResult<()>>;
+            /// Logs in, returning a session token that can be passed to
+            /// `resume` to log in again on a later connection.
+            async fn login(
+                &self,
+                name: String,
+                password: String,
+            ) -> HandlerResult<ServerResult<String>>;
+            async fn resume(&self, token: String) -> HandlerResult<ServerResult<String>>;
+            /// Creates a game hosted by the player, who joins it straight
+            /// away. Private games aren't listed, and can only be joined with
+            /// their join code.
+            async fn create_game(
+                &self,
+                capacity: u32,
+                mode: GameMode,
+                private: bool,
+            ) -> HandlerResult<ServerResult<GameInfo>>;
+            async fn list_games(&self, query: GameQuery) -> HandlerResult<ServerResult<GamePage>>;
+            async fn join_game(&self, game_id: GameID) -> HandlerResult<ServerResult<GameInfo>>;
+            /// Joins a game by its join code, e.g. `K7Q-4MX`. Case and dashes
+            /// don't matter.
+            async fn join_game_by_code(
+                &self,
+                code: String,
+            ) -> HandlerResult<ServerResult<GameInfo>>;
+            async fn leave_game(&self) -> HandlerResult<ServerResult<()>>;
+            /// Starts the game the player is hosting.
+            async fn start_game(&self) -> HandlerResult<ServerResult<()>>;
+            async fn update_x_position(&self, x: f32) -> HandlerResult<ServerResult<()>>;
+            async fn shoot(&self) -> HandlerResult<ServerResult<()>>;
+            async fn top_scores(
+                &self,
+                period: Period,
+                range: ScoreRange,
+            ) -> HandlerResult<ServerResult<Vec<RankedEntry>>>;
+            async fn my_rank(
+                &self,
+                period: Period,
+            ) -> HandlerResult<ServerResult<Option<RankedEntry>>>;
+        }
+
+        #[connection_state]
+        pub struct State {
+            /// Whether the client passed the `handshake`.
+            compatible: bool,
+            /// The name of the account this connection is logged in as. Only
+            /// set once the connection has authenticated with `login` or
+            /// `resume`.
+            name: Option<String>,
+            game_id: Option<GameID>,
+            current_x: f32,
+        }
+    };
+}
+
+#[codable]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct GameID(pub [u8; 16]);
+
+impl GameID {
+    pub fn new() -> Self {
+        Self(rand::random())
+    }
+}
+
+#[codable]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// The players share one formation and work together to clear it.
+    Coop,
+    /// The players share one formation and compete for the most points.
+    Versus,
+}
+
+#[codable]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    /// The game is waiting for its host to start it. Players can only join
+    /// while it's waiting.
+    Waiting,
+    InProgress,
+    Finished,
+}
+
+/// A game's record, as stored by the server and shown in the lobby.
+#[codable]
+#[derive(Debug, Clone)]
+pub struct GameInfo {
+    pub id: GameID,
+    /// The player who can start the game. If the host leaves, the player who
+    /// joined after them takes over.
+    pub host: String,
+    /// Every player in the game, in the order they joined.
+    pub players: Vec<String>,
+    /// The most players the game can hold.
+    pub capacity: u32,
+    pub mode: GameMode,
+    pub status: GameStatus,
+    /// When the game was created, in milliseconds since the unix epoch.
+    pub created: u64,
+    /// Private games are hidden from `list_games`.
+    pub private: bool,
+    /// A short code for the game that's easy to read out, like `K7Q-4MX`.
+    pub code: String,
+}
+
+/// What the lobby shows about a game.
+#[codable]
+#[derive(Debug, Clone)]
+pub struct GameSummary {
+    pub id: GameID,
+    pub host: String,
+    pub player_count: u32,
+    pub capacity: u32,
+    pub mode: GameMode,
+    pub status: GameStatus,
+}
+
+impl From<&GameInfo> for GameSummary {
+    fn from(game: &GameInfo) -> Self {
+        Self {
+            id: game.id,
+            host: game.host.clone(),
+            player_count: game.players.len() as u32,
+            capacity: game.capacity,
+            mode: game.mode,
+            status: game.status,
+        }
+    }
+}
+
+impl GameSummary {
+    /// Whether a player could join the game right now.
+    pub fn is_joinable(&self) -> bool {
+        self.status == GameStatus::Waiting && self.player_count < self.capacity
+    }
+}
+
+#[codable]
+#[derive(Debug, Clone, Default)]
+pub struct GameQuery {
+    /// Only list games that are waiting for players and have room for more.
+    pub joinable_only: bool,
+    /// Only list games of this mode.
+    pub mode: Option<GameMode>,
+    /// Where to carry on listing from, from [`GamePage::next`]. `None` starts
+    /// from the first page.
+    pub cursor: Option<Vec<u8>>,
+    /// How many games to return, at most [`MAX_GAMES_PAGE`].
+    pub count: u32,
+}
+
+/// A page of games, waiting games first and newest first within each status.
+#[codable]
+#[derive(Debug, Clone, Default)]
+pub struct GamePage {
+    pub games: Vec<GameSummary>,
+    /// The cursor for the next page, or `None` if this is the last page.
+    pub next: Option<Vec<u8>>,
+}
+
+/// The period a leaderboard covers. Daily and weekly boards start afresh at
+/// midnight UTC and on Monday respectively.
+#[codable]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Daily,
+    Weekly,
+    AllTime,
+}
+
+impl Period {
+    pub const ALL: [Period; 3] = [Period::Daily, Period::Weekly, Period::AllTime];
+}
+
+#[codable]
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreRange {
+    /// The rank to start from, where 0 is the best score.
+    pub start: u32,
+    /// How many entries to return, at most [`MAX_SCORE_RANGE`].
+    pub count: u32,
+}
+
+#[codable]
+#[derive(Debug, Clone)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: u32,
+    /// When the score was submitted, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+}
+
+#[codable]
+#[derive(Debug, Clone)]
+pub struct RankedEntry {
+    /// 1 for the best score on the board.
+    pub rank: u64,
+    pub entry: LeaderboardEntry,
+}
+
+/// An [`Event`] as it's sent to everyone in a game.
+#[codable]
+#[derive(Debug, Clone)]
+pub struct GameEvent {
+    pub game_id: GameID,
+    /// Counts up from 1 for each event sent in the game. Events are sent in
+    /// this order, so clients can drop any they've already seen.
+    pub seq: u64,
+    /// When the server sent the event, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+    pub event: Event,
+}
+
+/// Something that happened in a game.
+#[codable]
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A player fired a laser from `x`.
+    Laser {
+        by: String,
+        x: f32,
+    },
+    PlayerJoined {
+        name: String,
+    },
+    /// A player left the game. If they were the host, the next player to have
+    /// joined takes over.
+    PlayerLeft {
+        name: String,
+    },
+    /// A player's ship moved.
+    PlayerMoved {
+        name: String,
+        x: f32,
+    },
+    AlienKilled {
+        by: String,
+        x: f32,
+        y: f32,
+        points: u32,
+    },
+    /// A player's score went up, to `score`.
+    ScoreChanged {
+        name: String,
+        score: u32,
+    },
+    WaveStarted {
+        wave: u32,
+    },
+    WaveCleared {
+        wave: u32,
+    },
+    /// A player's ship was destroyed. `lives` is how many ships they have
+    /// left, so at 0 they're out of the game.
+    PlayerDied {
+        name: String,
+        lives: u32,
+    },
+    GameOver,
+    /// The host started the game.
+    GameStarted,
+    Snapshot(Snapshot),
+}
+
+impl From<SimEvent> for Event {
+    fn from(ev: SimEvent) -> Self {
+        match ev {
+            SimEvent::AlienKilled { by, pos, points } => Event::AlienKilled {
+                by,
+                x: pos.x,
+                y: pos.y,
+                points,
+            },
+            SimEvent::ScoreChanged { name, score } => Event::ScoreChanged { name, score },
+            SimEvent::WaveStarted { wave } => Event::WaveStarted { wave },
+            SimEvent::WaveCleared { wave } => Event::WaveCleared { wave },
+            SimEvent::ShipDestroyed { name, lives } => Event::PlayerDied { name, lives },
+            SimEvent::GameOver => Event::GameOver,
+        }
+    }
+}
+
+/// The authoritative state of a game, sent to its players every tick.
+#[codable]
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub wave: u32,
+    pub aliens: Vec<AlienSnapshot>,
+    pub lasers: Vec<LaserSnapshot>,
+    pub bombs: Vec<BombSnapshot>,
+    pub ships: Vec<ShipSnapshot>,
+    pub game_over: bool,
+}
+
+#[codable]
+#[derive(Debug, Clone)]
+pub struct AlienSnapshot {
+    pub x: f32,
+    pub y: f32,
+    pub sprite_index: u32,
+}
+
+#[codable]
+#[derive(Debug, Clone)]
+pub struct LaserSnapshot {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[codable]
+#[derive(Debug, Clone)]
+pub struct BombSnapshot {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[codable]
+#[derive(Debug, Clone)]
+pub struct ShipSnapshot {
+    pub name: String,
+    pub x: f32,
+    pub score: u32,
+    pub lives: u32,
+    /// Whether the ship is on screen, rather than destroyed and waiting to
+    /// respawn or out of lives.
+    pub in_play: bool,
+}
+
+impl From<&Simulation> for Snapshot {
+    fn from(sim: &Simulation) -> Self {
+        Self {
+            tick: sim.tick,
+            wave: sim.wave,
+            aliens: sim
+                .aliens
+                .iter()
+                .map(|alien| AlienSnapshot {
+                    x: alien.pos.x,
+                    y: alien.pos.y,
+                    sprite_index: alien.sprite_index as u32,
+                })
+                .collect(),
+            lasers: sim
+                .lasers
+                .iter()
+                .map(|laser| LaserSnapshot {
+                    x: laser.pos.x,
+                    y: laser.pos.y,
+                })
+                .collect(),
+            bombs: sim
+                .bombs
+                .iter()
+                .map(|bomb| BombSnapshot {
+                    x: bomb.pos.x,
+                    y: bomb.pos.y,
+                })
+                .collect(),
+            ships: sim
+                .ships
+                .iter()
+                .map(|ship| ShipSnapshot {
+                    name: ship.name.clone(),
+                    x: ship.x,
+                    score: ship.score,
+                    lives: ship.lives,
+                    in_play: ship.in_play(),
+                })
+                .collect(),
+            game_over: sim.game_over,
+        }
+    }
+}
+
+#[codable]
+#[derive(Debug)]
+pub enum Error {
+    NameTaken,
+    NameTooLong,
+    NameTooShort,
+    NameInvalid,
+    NameNotSet,
+    GameNotSet,
+    AlreadyInGame,
+    RangeTooLarge,
+    PasswordTooShort,
+    PasswordTooLong,
+    InvalidCredentials,
+    InvalidToken,
+    GameNotFound,
+    GameFull,
+    /// The game has already started, or has finished.
+    GameStarted,
+    GameNotStarted,
+    /// Only the host can do that.
+    NotHost,
+    /// Games on this server can be for between 1 and `max` players.
+    InvalidCapacity {
+        max: u32,
+    },
+    /// The server is running as many games as it can.
+    ServerFull,
+    /// The server couldn't read or write its storage.
+    Storage,
+    /// The client's protocol version doesn't match the server's.
+    IncompatibleVersion {
+        server: u32,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Error::NameTaken => "That name is taken",
+            Error::NameTooLong => "Names can be at most 32 characters",
+            Error::NameTooShort => "Enter a name",
+            Error::NameInvalid => "Names can only use letters and numbers",
+            Error::NameNotSet => "Log in first",
+            Error::GameNotSet => "Join a game first",
+            Error::AlreadyInGame => "Already in a game",
+            Error::RangeTooLarge => "Too many results requested",
+            Error::PasswordTooShort => "Passwords need at least 8 characters",
+            Error::PasswordTooLong => "Passwords can be at most 128 characters",
+            Error::InvalidCredentials => "Wrong name or password",
+            Error::InvalidToken => "Session expired, log in again",
+            Error::GameNotFound => "That game doesn't exist any more",
+            Error::GameFull => "That game is full",
+            Error::GameStarted => "That game has already started",
+            Error::GameNotStarted => "The game hasn't started yet",
+            Error::NotHost => "Only the host can start the game",
+            Error::InvalidCapacity { max } => {
+                return write!(f, "Games can have between 1 and {max} players");
+            }
+            Error::ServerFull => "The server is full, try again later",
+            Error::Storage => "Something went wrong on the server, try again",
+            Error::IncompatibleVersion { server } => {
+                return write!(
+                    f,
+                    "The server speaks protocol version {server} but this game speaks \
+                     {PROTOCOL_VERSION}, update the game to play online"
+                );
+            }
+        };
+        write!(f, "{msg}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type ServerResult<T> = Result<T, Error>;
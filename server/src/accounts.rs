@@ -0,0 +1,169 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::Utc;
+use cosmos_raiders_protocol::Error;
+use hardlight::*;
+use tracing::error;
+
+use crate::storage::Storage;
+
+/// How long a session token can be used to resume a session, in milliseconds.
+const SESSION_TTL_MS: u64 = 30 * 24 * 60 * 60 * 1000;
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 128;
+
+/// An account, found by its name.
+#[codable]
+#[derive(Clone)]
+pub struct Account {
+    /// The salted argon2 hash of the account's password, as a PHC string.
+    pub password_hash: String,
+    /// When the account was created, in milliseconds since the unix epoch.
+    pub created: u64,
+}
+
+/// A login session, found by its token.
+#[codable]
+#[derive(Clone)]
+pub struct Session {
+    pub name: String,
+    /// When the session stops being resumable, in milliseconds since the unix
+    /// epoch.
+    pub expires: u64,
+}
+
+fn now_ms() -> u64 {
+    Utc::now().timestamp_millis() as u64
+}
+
+pub fn validate_name(name: &str) -> Result<(), Error> {
+    if name.len() > 32 {
+        return Err(Error::NameTooLong);
+    }
+    if name.is_empty() {
+        return Err(Error::NameTooShort);
+    }
+    if name.contains(|c: char| !c.is_ascii_alphanumeric()) {
+        return Err(Error::NameInvalid);
+    }
+    Ok(())
+}
+
+fn validate_password(password: &str) -> Result<(), Error> {
+    if password.len() < MIN_PASSWORD_LEN {
+        return Err(Error::PasswordTooShort);
+    }
+    if password.len() > MAX_PASSWORD_LEN {
+        return Err(Error::PasswordTooLong);
+    }
+    Ok(())
+}
+
+/// Creates an account. The account still has to log in before it can play.
+pub fn register(storage: &dyn Storage, name: &str, password: &str) -> Result<(), Error> {
+    validate_name(name)?;
+    validate_password(password)?;
+
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| {
+            error!("Couldn't hash password for {name}: {e}");
+            Error::Storage
+        })?
+        .to_string();
+    let account = Account {
+        password_hash,
+        created: now_ms(),
+    };
+    if storage.insert_account(name, &account)? {
+        Ok(())
+    } else {
+        Err(Error::NameTaken)
+    }
+}
+
+/// Checks a name and password, returning a new session token if they match.
+pub fn login(storage: &dyn Storage, name: &str, password: &str) -> Result<String, Error> {
+    let Some(account) = storage.account(name)? else {
+        return Err(Error::InvalidCredentials);
+    };
+    // a hash that doesn't parse can't be checked against, and means the
+    // account's record is broken rather than the password being wrong
+    let hash = PasswordHash::new(&account.password_hash).map_err(|e| {
+        error!("Stored password hash for {name} is malformed: {e}");
+        Error::Storage
+    })?;
+    if Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_err()
+    {
+        return Err(Error::InvalidCredentials);
+    }
+
+    let token = new_token();
+    let session = Session {
+        name: name.to_string(),
+        expires: now_ms() + SESSION_TTL_MS,
+    };
+    storage.insert_session(&token, &session)?;
+    Ok(token)
+}
+
+/// Returns the name of the account a session token belongs to.
+pub fn resume(storage: &dyn Storage, token: &str) -> Result<String, Error> {
+    let Some(session) = storage.session(token)? else {
+        return Err(Error::InvalidToken);
+    };
+    if session.expires < now_ms() {
+        storage.remove_session(token)?;
+        return Err(Error::InvalidToken);
+    }
+    Ok(session.name)
+}
+
+/// Returns 32 random bytes, hex encoded.
+fn new_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn register_then_login() {
+        let storage = MemoryStorage::default();
+        register(&storage, "alice", "correct horse").unwrap();
+
+        let token = login(&storage, "alice", "correct horse").unwrap();
+        assert_eq!(resume(&storage, &token).unwrap(), "alice");
+        assert!(matches!(
+            login(&storage, "alice", "wrong horse"),
+            Err(Error::InvalidCredentials)
+        ));
+        assert!(matches!(
+            register(&storage, "alice", "another one"),
+            Err(Error::NameTaken)
+        ));
+    }
+
+    #[test]
+    fn malformed_stored_hash_is_an_error() {
+        let storage = MemoryStorage::default();
+        let account = Account {
+            password_hash: "not a phc string".to_string(),
+            created: 0,
+        };
+        storage.insert_account("bob", &account).unwrap();
+
+        assert!(matches!(
+            login(&storage, "bob", "whatever it is"),
+            Err(Error::Storage)
+        ));
+    }
+}
@@ -0,0 +1,642 @@
+use std::{
+    collections::HashSet,
+    error::Error as StdError,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use argon2::PasswordHash;
+use chrono::{TimeZone, Utc};
+use clap::Subcommand;
+use cosmos_raiders_protocol::{GameID, GameInfo, GameMode, GameStatus, LeaderboardEntry};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accounts::{self, Account, Session},
+    games,
+    storage::Storage,
+};
+
+/// Commands for looking after the database. The server shouldn't be running,
+/// as only one process can have the database open.
+#[derive(Subcommand, Debug)]
+pub enum AdminCommand {
+    /// Manage accounts
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Look at and close games
+    #[command(subcommand)]
+    Games(GamesCommand),
+    /// Manage the leaderboards
+    #[command(subcommand)]
+    Scores(ScoresCommand),
+    /// Copy the database to and from a JSON file
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UsersCommand {
+    /// List every account
+    List,
+    /// Delete an account, along with its sessions and scores, taking it out
+    /// of any games it's in
+    Delete { name: String },
+    /// Rename an account, keeping its password, sessions, scores and games
+    Rename { name: String, new_name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GamesCommand {
+    /// List every game, including private ones
+    List,
+    /// Show a game, found by its ID or join code
+    Show { game: String },
+    /// Remove a game, found by its ID or join code
+    Close { game: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ScoresCommand {
+    /// Clear every leaderboard
+    Reset {
+        /// Only take this player's scores off the leaderboards
+        #[arg(long)]
+        player: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbCommand {
+    /// Write everything in the database to a JSON file
+    Export { path: PathBuf },
+    /// Add everything in a file written by `db export` to the database
+    Import { path: PathBuf },
+}
+
+type AdminResult = Result<(), Box<dyn StdError>>;
+
+pub fn run(storage: &dyn Storage, command: AdminCommand) -> AdminResult {
+    match command {
+        AdminCommand::Users(UsersCommand::List) => list_users(storage),
+        AdminCommand::Users(UsersCommand::Delete { name }) => delete_user(storage, &name),
+        AdminCommand::Users(UsersCommand::Rename { name, new_name }) => {
+            rename_user(storage, &name, &new_name)
+        }
+        AdminCommand::Games(GamesCommand::List) => list_games(storage),
+        AdminCommand::Games(GamesCommand::Show { game }) => show_game(storage, &game),
+        AdminCommand::Games(GamesCommand::Close { game }) => close_game(storage, &game),
+        AdminCommand::Scores(ScoresCommand::Reset { player }) => {
+            reset_scores(storage, player.as_deref())
+        }
+        AdminCommand::Db(DbCommand::Export { path }) => export(storage, &path),
+        AdminCommand::Db(DbCommand::Import { path }) => import(storage, &path),
+    }
+}
+
+fn list_users(storage: &dyn Storage) -> AdminResult {
+    let mut accounts = storage.accounts()?;
+    accounts.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (name, account) in &accounts {
+        println!("{name:<32} created {}", format_time(account.created));
+    }
+    println!("{} accounts", accounts.len());
+    Ok(())
+}
+
+fn delete_user(storage: &dyn Storage, name: &str) -> AdminResult {
+    if !storage.remove_account(name)? {
+        return Err(format!("There's no account called {name}").into());
+    }
+    let mut sessions = 0;
+    for (token, session) in storage.sessions()? {
+        if session.name == name {
+            storage.remove_session(&token)?;
+            sessions += 1;
+        }
+    }
+    let mut boards = 0;
+    for board in storage.boards()? {
+        if storage.remove_score(&board, name)? {
+            boards += 1;
+        }
+    }
+    let mut games = 0;
+    for game in storage.games()? {
+        if game.players.iter().any(|p| p == name) {
+            games::leave(storage, game.id, name)?;
+            games += 1;
+        }
+    }
+    println!(
+        "Deleted {name}, {sessions} sessions and their scores on {boards} boards, and took them \
+         out of {games} games"
+    );
+    Ok(())
+}
+
+fn rename_user(storage: &dyn Storage, name: &str, new_name: &str) -> AdminResult {
+    accounts::validate_name(new_name)?;
+    let Some(account) = storage.account(name)? else {
+        return Err(format!("There's no account called {name}").into());
+    };
+    if !storage.insert_account(new_name, &account)? {
+        return Err(format!("{new_name} is already taken").into());
+    }
+    storage.remove_account(name)?;
+
+    for (token, session) in storage.sessions()? {
+        if session.name == name {
+            let session = Session {
+                name: new_name.to_string(),
+                ..session
+            };
+            storage.insert_session(&token, &session)?;
+        }
+    }
+    for board in storage.boards()? {
+        if let Some((_, entry)) = storage.best_score(&board, name)? {
+            storage.remove_score(&board, name)?;
+            let entry = LeaderboardEntry {
+                name: new_name.to_string(),
+                ..entry
+            };
+            storage.submit_score(&board, &entry)?;
+        }
+    }
+    for game in storage.games()? {
+        if !game.players.iter().any(|p| p == name) {
+            continue;
+        }
+        storage.update_game(game.id, &|game| {
+            for player in game.players.iter_mut().chain([&mut game.host]) {
+                if *player == name {
+                    *player = new_name.to_string();
+                }
+            }
+            Ok(())
+        })?;
+    }
+    println!("Renamed {name} to {new_name}");
+    Ok(())
+}
+
+fn list_games(storage: &dyn Storage) -> AdminResult {
+    let mut games = storage.games()?;
+    games.sort_by_key(|game| game.created);
+    for game in &games {
+        println!(
+            "{}  {:<11} {:<6} {}/{}  hosted by {}{}",
+            hex(&game.id.0),
+            status_name(game.status),
+            mode_name(game.mode),
+            game.players.len(),
+            game.capacity,
+            game.host,
+            if game.private { ", private" } else { "" },
+        );
+    }
+    println!("{} games", games.len());
+    Ok(())
+}
+
+fn show_game(storage: &dyn Storage, game: &str) -> AdminResult {
+    let game_id = find_game(storage, game)?;
+    let Some(game) = storage.games()?.into_iter().find(|game| game.id == game_id) else {
+        return Err("That game doesn't exist".into());
+    };
+    println!("ID:        {}", hex(&game.id.0));
+    println!("Join code: {}", game.code);
+    println!("Created:   {}", format_time(game.created));
+    println!("Mode:      {}", mode_name(game.mode));
+    println!("Status:    {}", status_name(game.status));
+    println!("Private:   {}", game.private);
+    println!("Host:      {}", game.host);
+    println!("Players:   {}/{}", game.players.len(), game.capacity);
+    for player in &game.players {
+        println!("  {player}");
+    }
+    Ok(())
+}
+
+fn close_game(storage: &dyn Storage, game: &str) -> AdminResult {
+    let game_id = find_game(storage, game)?;
+    if !storage.remove_game(game_id)? {
+        return Err("That game doesn't exist".into());
+    }
+    println!("Closed game {}", hex(&game_id.0));
+    Ok(())
+}
+
+fn reset_scores(storage: &dyn Storage, player: Option<&str>) -> AdminResult {
+    let mut boards = 0;
+    for board in storage.boards()? {
+        match player {
+            Some(player) => {
+                if storage.remove_score(&board, player)? {
+                    boards += 1;
+                }
+            }
+            None => {
+                storage.remove_board(&board)?;
+                boards += 1;
+            }
+        }
+    }
+    match player {
+        Some(player) => println!("Took {player} off {boards} boards"),
+        None => println!("Cleared {boards} boards"),
+    }
+    Ok(())
+}
+
+/// Finds a game from either its ID, as shown by `games list`, or its join
+/// code.
+fn find_game(storage: &dyn Storage, game: &str) -> Result<GameID, Box<dyn StdError>> {
+    if let Some(bytes) = unhex(game) {
+        if let Ok(id) = bytes.try_into() {
+            return Ok(GameID(id));
+        }
+    }
+    Ok(games::find_by_code(storage, game)?)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Formats a time in milliseconds since the unix epoch, e.g.
+/// `2023-10-01 18:30`.
+fn format_time(ms: u64) -> String {
+    match Utc.timestamp_millis_opt(ms as i64).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => ms.to_string(),
+    }
+}
+
+fn mode_name(mode: GameMode) -> &'static str {
+    ModeDump::from(mode).name()
+}
+
+fn status_name(status: GameStatus) -> &'static str {
+    StatusDump::from(status).name()
+}
+
+/// The version of the dump format, which is separate from the database's
+/// schema version. It only needs bumping if a dump written by this server
+/// couldn't be read by an older one.
+const DUMP_FORMAT: u32 = 1;
+
+/// The `db export` file. It describes what's stored rather than how it's
+/// stored, so a dump can be imported into a database with a different schema
+/// version than the one it was exported from. Fields added later should have
+/// defaults, so older dumps can still be read.
+#[derive(Serialize, Deserialize)]
+struct Dump {
+    format: u32,
+    #[serde(default)]
+    accounts: Vec<AccountDump>,
+    #[serde(default)]
+    sessions: Vec<SessionDump>,
+    #[serde(default)]
+    games: Vec<GameDump>,
+    #[serde(default)]
+    scores: Vec<ScoreDump>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AccountDump {
+    name: String,
+    password_hash: String,
+    created: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionDump {
+    token: String,
+    name: String,
+    expires: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GameDump {
+    /// The game's ID, in hex.
+    id: String,
+    host: String,
+    players: Vec<String>,
+    capacity: u32,
+    mode: ModeDump,
+    status: StatusDump,
+    created: u64,
+    private: bool,
+    code: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum ModeDump {
+    Coop,
+    Versus,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum StatusDump {
+    Waiting,
+    InProgress,
+    Finished,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScoreDump {
+    board: String,
+    name: String,
+    score: u32,
+    timestamp: u64,
+}
+
+impl ModeDump {
+    fn name(self) -> &'static str {
+        match self {
+            ModeDump::Coop => "coop",
+            ModeDump::Versus => "versus",
+        }
+    }
+}
+
+impl From<GameMode> for ModeDump {
+    fn from(mode: GameMode) -> Self {
+        match mode {
+            GameMode::Coop => ModeDump::Coop,
+            GameMode::Versus => ModeDump::Versus,
+        }
+    }
+}
+
+impl From<ModeDump> for GameMode {
+    fn from(mode: ModeDump) -> Self {
+        match mode {
+            ModeDump::Coop => GameMode::Coop,
+            ModeDump::Versus => GameMode::Versus,
+        }
+    }
+}
+
+impl StatusDump {
+    fn name(self) -> &'static str {
+        match self {
+            StatusDump::Waiting => "waiting",
+            StatusDump::InProgress => "in-progress",
+            StatusDump::Finished => "finished",
+        }
+    }
+}
+
+impl From<GameStatus> for StatusDump {
+    fn from(status: GameStatus) -> Self {
+        match status {
+            GameStatus::Waiting => StatusDump::Waiting,
+            GameStatus::InProgress => StatusDump::InProgress,
+            GameStatus::Finished => StatusDump::Finished,
+        }
+    }
+}
+
+impl From<StatusDump> for GameStatus {
+    fn from(status: StatusDump) -> Self {
+        match status {
+            StatusDump::Waiting => GameStatus::Waiting,
+            StatusDump::InProgress => GameStatus::InProgress,
+            StatusDump::Finished => GameStatus::Finished,
+        }
+    }
+}
+
+fn export(storage: &dyn Storage, path: &Path) -> AdminResult {
+    let accounts = storage
+        .accounts()?
+        .into_iter()
+        .map(|(name, account)| AccountDump {
+            name,
+            password_hash: account.password_hash,
+            created: account.created,
+        })
+        .collect();
+    let sessions = storage
+        .sessions()?
+        .into_iter()
+        .map(|(token, session)| SessionDump {
+            token,
+            name: session.name,
+            expires: session.expires,
+        })
+        .collect();
+    let games = storage
+        .games()?
+        .into_iter()
+        .map(|game| GameDump {
+            id: hex(&game.id.0),
+            host: game.host,
+            players: game.players,
+            capacity: game.capacity,
+            mode: game.mode.into(),
+            status: game.status.into(),
+            created: game.created,
+            private: game.private,
+            code: game.code,
+        })
+        .collect();
+    let mut scores = Vec::new();
+    for board in storage.boards()? {
+        for entry in storage.scores(&board, 0, usize::MAX)? {
+            scores.push(ScoreDump {
+                board: board.clone(),
+                name: entry.name,
+                score: entry.score,
+                timestamp: entry.timestamp,
+            });
+        }
+    }
+    let dump = Dump {
+        format: DUMP_FORMAT,
+        accounts,
+        sessions,
+        games,
+        scores,
+    };
+    fs::write(path, serde_json::to_string_pretty(&dump)?)?;
+    println!(
+        "Exported {} accounts, {} sessions, {} games and {} scores to {}",
+        dump.accounts.len(),
+        dump.sessions.len(),
+        dump.games.len(),
+        dump.scores.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Adds a dump to the database. Accounts and games that are already in it
+/// are left alone, and scores only replace worse ones.
+fn import(storage: &dyn Storage, path: &Path) -> AdminResult {
+    let dump: Dump = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if dump.format > DUMP_FORMAT {
+        return Err(format!(
+            "The dump is in format {}, but this server only reads up to {DUMP_FORMAT}",
+            dump.format
+        )
+        .into());
+    }
+    let counts = (
+        dump.accounts.len(),
+        dump.sessions.len(),
+        dump.games.len(),
+        dump.scores.len(),
+    );
+
+    // check every hash before writing anything, so a bad dump doesn't leave
+    // half of itself behind or accounts nobody can log in to
+    let bad: Vec<&str> = dump
+        .accounts
+        .iter()
+        .filter(|account| PasswordHash::new(&account.password_hash).is_err())
+        .map(|account| account.name.as_str())
+        .collect();
+    if !bad.is_empty() {
+        return Err(format!(
+            "These accounts' password hashes aren't valid PHC strings: {}",
+            bad.join(", ")
+        )
+        .into());
+    }
+
+    let mut skipped = 0;
+    for account in &dump.accounts {
+        let stored = Account {
+            password_hash: account.password_hash.clone(),
+            created: account.created,
+        };
+        if !storage.insert_account(&account.name, &stored)? {
+            skipped += 1;
+        }
+    }
+    for session in &dump.sessions {
+        let stored = Session {
+            name: session.name.clone(),
+            expires: session.expires,
+        };
+        storage.insert_session(&session.token, &stored)?;
+    }
+    let existing: HashSet<GameID> = storage.games()?.iter().map(|game| game.id).collect();
+    for game in dump.games {
+        let Some(id) = unhex(&game.id).and_then(|bytes| bytes.try_into().ok()) else {
+            return Err(format!("{:?} isn't a game ID", game.id).into());
+        };
+        let stored = GameInfo {
+            id: GameID(id),
+            host: game.host,
+            players: game.players,
+            capacity: game.capacity,
+            mode: game.mode.into(),
+            status: game.status.into(),
+            created: game.created,
+            private: game.private,
+            code: game.code,
+        };
+        if existing.contains(&stored.id) || !storage.insert_game(&stored)? {
+            skipped += 1;
+        }
+    }
+    for score in &dump.scores {
+        let entry = LeaderboardEntry {
+            name: score.name.clone(),
+            score: score.score,
+            timestamp: score.timestamp,
+        };
+        storage.submit_score(&score.board, &entry)?;
+    }
+    let (accounts, sessions, games, scores) = counts;
+    println!(
+        "Imported {accounts} accounts, {sessions} sessions, {games} games and {scores} scores \
+         from {}, skipping {skipped} that were already there",
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use argon2::{
+        password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+        Argon2,
+    };
+
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn write_dump(name: &str, dump: &Dump) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.json", std::process::id()));
+        fs::write(&path, serde_json::to_string(dump).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn import_rejects_malformed_hashes() {
+        let storage = MemoryStorage::default();
+        let dump = Dump {
+            format: DUMP_FORMAT,
+            accounts: vec![
+                AccountDump {
+                    name: "alice".to_string(),
+                    password_hash: Argon2::default()
+                        .hash_password(b"correct horse", &SaltString::generate(&mut OsRng))
+                        .unwrap()
+                        .to_string(),
+                    created: 0,
+                },
+                AccountDump {
+                    name: "bob".to_string(),
+                    password_hash: "hunter2".to_string(),
+                    created: 0,
+                },
+            ],
+            sessions: Vec::new(),
+            games: Vec::new(),
+            scores: Vec::new(),
+        };
+        let path = write_dump("import-bad-hash", &dump);
+        let result = import(&storage, &path);
+        fs::remove_file(&path).unwrap();
+
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("bob") && !err.contains("alice"), "{err}");
+        assert!(storage.accounts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn deleting_a_user_takes_them_out_of_their_games() {
+        let storage = MemoryStorage::default();
+        accounts::register(&storage, "alice", "correct horse").unwrap();
+        let shared = games::create(&storage, "alice", 4, GameMode::Coop, false, 4).unwrap();
+        games::join(&storage, shared.id, "bob", false).unwrap();
+        games::create(&storage, "alice", 4, GameMode::Coop, false, 4).unwrap();
+
+        delete_user(&storage, "alice").unwrap();
+
+        let games = storage.games().unwrap();
+        assert_eq!(games.len(), 1, "alice's solo game should be gone");
+        assert_eq!(games[0].id, shared.id);
+        assert_eq!(games[0].players, vec!["bob".to_string()]);
+        assert_eq!(games[0].host, "bob");
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, LazyLock, Mutex, OnceLock},
+};
+
+use chrono::Utc;
+use cosmos_raiders_protocol::{Event, GameEvent, GameID};
+use hardlight::{tokio, EventEmitter};
+
+use crate::topic;
+
+static EMITTER: OnceLock<EventEmitter> = OnceLock::new();
+
+static SEQUENCER: LazyLock<Sequencer> = LazyLock::new(Sequencer::default);
+
+/// Sets up sending events, once the server has been created.
+pub fn init(emitter: EventEmitter) {
+    if EMITTER.set(emitter).is_err() {
+        unreachable!("events set up twice");
+    }
+}
+
+/// Sends events to everyone in a game, numbered in the order given and
+/// stamped with the time.
+pub async fn emit(game_id: GameID, events: Vec<Event>) {
+    let emitter = EMITTER.get().expect("events not set up");
+    let topic = topic(game_id);
+    SEQUENCER
+        .send(game_id, events, |ev| emitter.emit(&topic, ev))
+        .await;
+}
+
+/// Forgets a game's sequence numbers once it's gone.
+pub fn forget(game_id: GameID) {
+    SEQUENCER.forget(game_id);
+}
+
+/// Numbers each game's events, and makes sure they're sent in the order
+/// they're numbered.
+#[derive(Default)]
+struct Sequencer {
+    /// The sequence number of the last event sent in each game. Its lock is
+    /// held until the events it numbers have been sent, so concurrent sends
+    /// to the same game can't overtake each other.
+    games: Mutex<HashMap<GameID, Arc<tokio::sync::Mutex<u64>>>>,
+}
+
+impl Sequencer {
+    async fn send<F, Fut>(&self, game_id: GameID, events: Vec<Event>, mut send: F)
+    where
+        F: FnMut(GameEvent) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let last = self
+            .games
+            .lock()
+            .unwrap()
+            .entry(game_id)
+            .or_default()
+            .clone();
+        let mut seq = last.lock().await;
+        let timestamp = Utc::now().timestamp_millis() as u64;
+        for event in events {
+            *seq += 1;
+            send(GameEvent {
+                game_id,
+                seq: *seq,
+                timestamp,
+                event,
+            })
+            .await;
+        }
+    }
+
+    fn forget(&self, game_id: GameID) {
+        self.games.lock().unwrap().remove(&game_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(n: u8) -> GameID {
+        GameID([n; 16])
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_sends_go_out_in_sequence_order() {
+        let sequencer = Arc::new(Sequencer::default());
+        let sent = Arc::new(Mutex::new(Vec::new()));
+
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let sequencer = sequencer.clone();
+            let sent = sent.clone();
+            tasks.push(tokio::spawn(async move {
+                let events = (0..10).map(|wave| Event::WaveStarted { wave }).collect();
+                sequencer
+                    .send(game(1), events, |ev| {
+                        let sent = sent.clone();
+                        async move {
+                            // give other sends every chance to overtake
+                            tokio::task::yield_now().await;
+                            sent.lock().unwrap().push((ev.game_id, ev.seq));
+                        }
+                    })
+                    .await;
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let seqs: Vec<u64> = sent.lock().unwrap().iter().map(|&(_, seq)| seq).collect();
+        assert_eq!(seqs, (1..=80).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn each_game_counts_from_one() {
+        let sequencer = Sequencer::default();
+        let sent = Mutex::new(Vec::new());
+        let record = |ev: GameEvent| {
+            sent.lock().unwrap().push((ev.game_id, ev.seq));
+            async {}
+        };
+
+        sequencer
+            .send(game(1), vec![Event::GameStarted, Event::GameOver], record)
+            .await;
+        sequencer
+            .send(game(2), vec![Event::GameStarted], record)
+            .await;
+        sequencer.forget(game(1));
+        sequencer
+            .send(game(1), vec![Event::GameStarted], record)
+            .await;
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![(game(1), 1), (game(1), 2), (game(2), 1), (game(1), 1)]
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::{Parser, Subcommand};
+use cosmos_raiders_protocol::MAX_PLAYERS;
+use serde::Deserialize;
+use tracing::Level;
+
+use crate::admin::AdminCommand;
+
+/// The fastest the server can be configured to tick, in ticks per second.
+const MAX_TICK_RATE: u32 = 120;
+
+#[derive(Parser, Debug)]
+#[command(about = "The Cosmos Raiders game server")]
+struct Cli {
+    #[command(flatten)]
+    args: Args,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// What the server binary should do.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the game server. This is the default
+    Serve,
+    #[command(flatten)]
+    Admin(AdminCommand),
+}
+
+/// Command line options. Each one overrides the matching setting in the
+/// config file.
+#[derive(clap::Args, Debug)]
+struct Args {
+    /// A TOML file to read settings from
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// The address to listen on, e.g. 0.0.0.0:8080
+    #[arg(long)]
+    bind: Option<String>,
+    /// A PEM file with the TLS certificate chain. A self-signed certificate is
+    /// used if this and --tls-key aren't given
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// A PEM file with the TLS private key
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Where to keep the database
+    #[arg(long)]
+    db_path: Option<PathBuf>,
+    /// Keep everything in memory instead of a database, so nothing is saved
+    /// when the server stops
+    #[arg(long)]
+    in_memory: bool,
+    /// Report how the database would be upgraded for this server, then exit
+    /// without changing it
+    #[arg(long)]
+    dry_run_migrations: bool,
+    /// One of trace, debug, info, warn or error
+    #[arg(long)]
+    log_level: Option<String>,
+    /// The most games that can be in progress at once
+    #[arg(long)]
+    max_games: Option<usize>,
+    /// The most players a game can be created for
+    #[arg(long)]
+    max_players: Option<u32>,
+    /// How many times a second games are stepped
+    #[arg(long)]
+    tick_rate: Option<u32>,
+    /// How often to look for abandoned games, in seconds
+    #[arg(long)]
+    sweep_interval: Option<u64>,
+    /// How long a game can go without any connected players before it's
+    /// removed, in seconds
+    #[arg(long)]
+    abandoned_timeout: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// The server's settings, from the config file and command line.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    /// The certificate to serve. `None` uses a self-signed certificate, which
+    /// is only suitable for local testing.
+    pub tls: Option<TlsConfig>,
+    pub db_path: PathBuf,
+    /// Whether to keep everything in memory instead of at `db_path`, for
+    /// testing.
+    pub in_memory: bool,
+    /// Whether to only report how the database would be upgraded. This can
+    /// only be set from the command line.
+    #[serde(skip)]
+    pub dry_run_migrations: bool,
+    pub log_level: String,
+    pub max_games: usize,
+    pub max_players: u32,
+    pub tick_rate: u32,
+    /// How often to look for abandoned games, in seconds.
+    pub sweep_interval: u64,
+    /// How long a game can go without any connected players before it's
+    /// removed, in seconds.
+    pub abandoned_timeout: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: "localhost:8080".to_string(),
+            tls: None,
+            db_path: PathBuf::from("cr.db"),
+            in_memory: false,
+            dry_run_migrations: false,
+            log_level: "info".to_string(),
+            max_games: 100,
+            max_players: MAX_PLAYERS,
+            tick_rate: 20,
+            sweep_interval: 60,
+            abandoned_timeout: 5 * 60,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    InvalidBind(String),
+    MissingTlsFile(PathBuf),
+    InvalidLogLevel(String),
+    NoGames,
+    InvalidMaxPlayers(u32),
+    InvalidTickRate(u32),
+    NoSweepInterval,
+    NoAbandonedTimeout,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => {
+                write!(f, "couldn't read the config file {}: {e}", path.display())
+            }
+            ConfigError::Parse(path, e) => {
+                write!(f, "couldn't parse the config file {}: {e}", path.display())
+            }
+            ConfigError::InvalidBind(bind) => {
+                write!(f, "bind address {bind:?} isn't a valid host:port")
+            }
+            ConfigError::MissingTlsFile(path) => {
+                write!(f, "TLS file {} doesn't exist", path.display())
+            }
+            ConfigError::InvalidLogLevel(level) => write!(
+                f,
+                "log level {level:?} should be one of trace, debug, info, warn or error"
+            ),
+            ConfigError::NoGames => write!(f, "max games must be at least 1"),
+            ConfigError::InvalidMaxPlayers(n) => {
+                write!(
+                    f,
+                    "max players is {n}, but must be between 1 and {MAX_PLAYERS}"
+                )
+            }
+            ConfigError::InvalidTickRate(n) => {
+                write!(
+                    f,
+                    "tick rate is {n}, but must be between 1 and {MAX_TICK_RATE}"
+                )
+            }
+            ConfigError::NoSweepInterval => write!(f, "sweep interval must be at least 1"),
+            ConfigError::NoAbandonedTimeout => write!(f, "abandoned timeout must be at least 1"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the config from the command line and the config file it points
+    /// to, if any, along with the command to run.
+    pub fn load() -> Result<(Self, Command), ConfigError> {
+        let Cli { args, command } = Cli::parse();
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_args(args);
+        config.validate()?;
+        Ok((config, command.unwrap_or(Command::Serve)))
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    fn apply_args(&mut self, args: Args) {
+        if let Some(bind) = args.bind {
+            self.bind = bind;
+        }
+        if let (Some(cert), Some(key)) = (args.tls_cert, args.tls_key) {
+            self.tls = Some(TlsConfig { cert, key });
+        }
+        if let Some(db_path) = args.db_path {
+            self.db_path = db_path;
+        }
+        if args.in_memory {
+            self.in_memory = true;
+        }
+        self.dry_run_migrations = args.dry_run_migrations;
+        if let Some(log_level) = args.log_level {
+            self.log_level = log_level;
+        }
+        if let Some(max_games) = args.max_games {
+            self.max_games = max_games;
+        }
+        if let Some(max_players) = args.max_players {
+            self.max_players = max_players;
+        }
+        if let Some(tick_rate) = args.tick_rate {
+            self.tick_rate = tick_rate;
+        }
+        if let Some(sweep_interval) = args.sweep_interval {
+            self.sweep_interval = sweep_interval;
+        }
+        if let Some(abandoned_timeout) = args.abandoned_timeout {
+            self.abandoned_timeout = abandoned_timeout;
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !self
+            .bind
+            .to_socket_addrs()
+            .is_ok_and(|mut addrs| addrs.next().is_some())
+        {
+            return Err(ConfigError::InvalidBind(self.bind.clone()));
+        }
+        if let Some(tls) = &self.tls {
+            for path in [&tls.cert, &tls.key] {
+                if !path.is_file() {
+                    return Err(ConfigError::MissingTlsFile(path.clone()));
+                }
+            }
+        }
+        self.log_level()?;
+        if self.max_games == 0 {
+            return Err(ConfigError::NoGames);
+        }
+        if !(1..=MAX_PLAYERS).contains(&self.max_players) {
+            return Err(ConfigError::InvalidMaxPlayers(self.max_players));
+        }
+        if !(1..=MAX_TICK_RATE).contains(&self.tick_rate) {
+            return Err(ConfigError::InvalidTickRate(self.tick_rate));
+        }
+        if self.sweep_interval == 0 {
+            return Err(ConfigError::NoSweepInterval);
+        }
+        if self.abandoned_timeout == 0 {
+            return Err(ConfigError::NoAbandonedTimeout);
+        }
+        Ok(())
+    }
+
+    pub fn log_level(&self) -> Result<Level, ConfigError> {
+        Level::from_str(&self.log_level)
+            .map_err(|_| ConfigError::InvalidLogLevel(self.log_level.clone()))
+    }
+
+    /// The hardlight server config for the bind address and certificate.
+    pub fn server_config(&self) -> Result<hardlight::ServerConfig, ConfigError> {
+        let Some(tls) = &self.tls else {
+            return Ok(hardlight::ServerConfig::new_self_signed(&self.bind));
+        };
+        let read = |path: &PathBuf| {
+            File::open(path)
+                .map(BufReader::new)
+                .map_err(|e| ConfigError::Read(path.clone(), e))
+        };
+        let certs = rustls_pemfile::certs(&mut read(&tls.cert)?)
+            .map_err(|e| ConfigError::Read(tls.cert.clone(), e))?
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut read(&tls.key)?)
+            .map_err(|e| ConfigError::Read(tls.key.clone(), e))?
+            .into_iter()
+            .next()
+            .map(rustls::PrivateKey)
+            .ok_or_else(|| {
+                ConfigError::Read(
+                    tls.key.clone(),
+                    io::Error::new(io::ErrorKind::InvalidData, "no PKCS #8 private key found"),
+                )
+            })?;
+        let tls_config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| {
+                ConfigError::Read(
+                    tls.cert.clone(),
+                    io::Error::new(io::ErrorKind::InvalidData, e),
+                )
+            })?;
+        Ok(hardlight::ServerConfig {
+            address: self.bind.clone(),
+            tls: tls_config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Validates the default config with one change made to it.
+    fn validate_with(change: impl FnOnce(&mut Config)) -> Result<(), ConfigError> {
+        let mut config = Config {
+            bind: "127.0.0.1:8080".to_string(),
+            ..Config::default()
+        };
+        change(&mut config);
+        config.validate()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        validate_with(|_| {}).unwrap();
+    }
+
+    #[test]
+    fn each_setting_is_checked() {
+        assert!(matches!(
+            validate_with(|c| c.bind = "no port".to_string()),
+            Err(ConfigError::InvalidBind(_))
+        ));
+        assert!(matches!(
+            validate_with(|c| {
+                c.tls = Some(TlsConfig {
+                    cert: PathBuf::from("/nonexistent/cert.pem"),
+                    key: PathBuf::from("/nonexistent/key.pem"),
+                })
+            }),
+            Err(ConfigError::MissingTlsFile(_))
+        ));
+        assert!(matches!(
+            validate_with(|c| c.log_level = "loud".to_string()),
+            Err(ConfigError::InvalidLogLevel(_))
+        ));
+        assert!(matches!(
+            validate_with(|c| c.max_games = 0),
+            Err(ConfigError::NoGames)
+        ));
+        for max_players in [0, MAX_PLAYERS + 1] {
+            assert!(matches!(
+                validate_with(|c| c.max_players = max_players),
+                Err(ConfigError::InvalidMaxPlayers(n)) if n == max_players
+            ));
+        }
+        for tick_rate in [0, MAX_TICK_RATE + 1] {
+            assert!(matches!(
+                validate_with(|c| c.tick_rate = tick_rate),
+                Err(ConfigError::InvalidTickRate(n)) if n == tick_rate
+            ));
+        }
+        assert!(matches!(
+            validate_with(|c| c.sweep_interval = 0),
+            Err(ConfigError::NoSweepInterval)
+        ));
+        assert!(matches!(
+            validate_with(|c| c.abandoned_timeout = 0),
+            Err(ConfigError::NoAbandonedTimeout)
+        ));
+    }
+
+    #[test]
+    fn limits_are_inclusive() {
+        validate_with(|c| {
+            c.max_players = MAX_PLAYERS;
+            c.tick_rate = MAX_TICK_RATE;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn files_fill_in_defaults_and_reject_unknown_settings() {
+        let config: Config =
+            toml::from_str("max_games = 5\n[tls]\ncert = \"a\"\nkey = \"b\"").unwrap();
+        assert_eq!(config.max_games, 5);
+        assert_eq!(config.tick_rate, Config::default().tick_rate);
+        assert_eq!(config.tls.unwrap().cert, PathBuf::from("a"));
+
+        assert!(toml::from_str::<Config>("max_gmaes = 5").is_err());
+    }
+
+    #[test]
+    fn args_override_the_file() {
+        let mut config: Config = toml::from_str("max_games = 5\ntick_rate = 30").unwrap();
+        let Cli { args, .. } =
+            Cli::try_parse_from(["server", "--max-games", "7", "--dry-run-migrations"]).unwrap();
+        config.apply_args(args);
+
+        assert_eq!(config.max_games, 7);
+        assert_eq!(config.tick_rate, 30);
+        assert!(config.dry_run_migrations);
+    }
+}
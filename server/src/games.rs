@@ -0,0 +1,297 @@
+use chrono::Utc;
+use cosmos_raiders_protocol::{
+    Error, GameID, GameInfo, GameMode, GamePage, GameQuery, GameStatus, MAX_GAMES_PAGE,
+};
+use tracing::error;
+
+use crate::storage::Storage;
+
+/// The characters join codes are made of. Letters and digits that are easily
+/// mixed up when read out, like O and 0, are left out.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 6;
+/// How many join codes to try before giving up on creating a game. With
+/// nearly 900 million codes, running out means the storage is refusing every
+/// code rather than them all being taken.
+const MAX_CODE_ATTEMPTS: usize = 16;
+
+/// Returns a random join code like `K7Q-4MX`.
+fn new_code() -> String {
+    let mut code = String::with_capacity(CODE_LEN + 1);
+    for i in 0..CODE_LEN {
+        if i == CODE_LEN / 2 {
+            code.push('-');
+        }
+        let c = CODE_ALPHABET[rand::random::<usize>() % CODE_ALPHABET.len()];
+        code.push(c as char);
+    }
+    code
+}
+
+/// Puts a join code typed in by a player into the form it's stored in, so
+/// `k7q4mx` and `K7Q 4MX` both find `K7Q-4MX`.
+fn normalize_code(code: &str) -> Option<String> {
+    let chars: Vec<char> = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if chars.len() != CODE_LEN {
+        return None;
+    }
+    let (first, second) = chars.split_at(CODE_LEN / 2);
+    Some(format!(
+        "{}-{}",
+        first.iter().collect::<String>(),
+        second.iter().collect::<String>()
+    ))
+}
+
+/// Creates a game hosted by `host`, who is its first player. Private games
+/// are left out of the lobby index.
+pub fn create(
+    storage: &dyn Storage,
+    host: &str,
+    capacity: u32,
+    mode: GameMode,
+    private: bool,
+    max_players: u32,
+) -> Result<GameInfo, Error> {
+    if !(1..=max_players).contains(&capacity) {
+        return Err(Error::InvalidCapacity { max: max_players });
+    }
+    let game = GameInfo {
+        id: GameID::new(),
+        host: host.to_string(),
+        players: vec![host.to_string()],
+        capacity,
+        mode,
+        status: GameStatus::Waiting,
+        created: Utc::now().timestamp_millis() as u64,
+        private,
+        code: String::new(),
+    };
+    insert_with_code(storage, game, new_code)
+}
+
+/// Stores a new game under a code from `new_code`, picking another whenever
+/// the code is already in use. The storage checks for the code and adds it in
+/// one step, so two games can't claim the same code.
+fn insert_with_code(
+    storage: &dyn Storage,
+    mut game: GameInfo,
+    mut new_code: impl FnMut() -> String,
+) -> Result<GameInfo, Error> {
+    for _ in 0..MAX_CODE_ATTEMPTS {
+        game.code = new_code();
+        if storage.insert_game(&game)? {
+            return Ok(game);
+        }
+    }
+    error!("Couldn't find an unused join code in {MAX_CODE_ATTEMPTS} attempts");
+    Err(Error::Storage)
+}
+
+/// Looks up the game a join code belongs to.
+pub fn find_by_code(storage: &dyn Storage, code: &str) -> Result<GameID, Error> {
+    let code = normalize_code(code).ok_or(Error::GameNotFound)?;
+    storage.game_by_code(&code)?.ok_or(Error::GameNotFound)
+}
+
+/// Lists a page of games from the lobby index.
+pub fn list(storage: &dyn Storage, query: GameQuery) -> Result<GamePage, Error> {
+    if query.count > MAX_GAMES_PAGE {
+        return Err(Error::RangeTooLarge);
+    }
+    let mut page = GamePage::default();
+    // joinable games are all waiting, so only that part of the index is read
+    storage.scan_games(
+        query.cursor.as_deref(),
+        query.joinable_only,
+        &mut |key, summary| {
+            if page.games.len() as u32 == query.count {
+                return false;
+            }
+            page.next = Some(key.to_vec());
+            if query.joinable_only && !summary.is_joinable() {
+                return true;
+            }
+            if query.mode.is_some_and(|mode| mode != summary.mode) {
+                return true;
+            }
+            page.games.push(summary);
+            true
+        },
+    )?;
+    if (page.games.len() as u32) < query.count {
+        page.next = None;
+    }
+    Ok(page)
+}
+
+/// Adds a player to a game that hasn't started yet. Private games can only be
+/// joined `by_code`.
+pub fn join(
+    storage: &dyn Storage,
+    game_id: GameID,
+    name: &str,
+    by_code: bool,
+) -> Result<GameInfo, Error> {
+    let game = storage.update_game(game_id, &|game| {
+        if game.private && !by_code {
+            return Err(Error::GameNotFound);
+        }
+        if game.status != GameStatus::Waiting {
+            return Err(Error::GameStarted);
+        }
+        if game.players.iter().any(|p| p == name) {
+            return Err(Error::AlreadyInGame);
+        }
+        if game.players.len() as u32 >= game.capacity {
+            return Err(Error::GameFull);
+        }
+        game.players.push(name.to_string());
+        Ok(())
+    })?;
+    // a game with a player in it is never removed
+    Ok(game.unwrap())
+}
+
+/// Removes a player from a game. If they were the host, the next player to
+/// have joined takes over. Returns `None` if the game was removed because
+/// nobody is left in it.
+pub fn leave(
+    storage: &dyn Storage,
+    game_id: GameID,
+    name: &str,
+) -> Result<Option<GameInfo>, Error> {
+    storage.update_game(game_id, &|game| {
+        game.players.retain(|p| p != name);
+        if game.host == name {
+            if let Some(next) = game.players.first() {
+                game.host = next.clone();
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Starts a game. Only its host can start it.
+pub fn start(storage: &dyn Storage, game_id: GameID, name: &str) -> Result<GameInfo, Error> {
+    let game = storage.update_game(game_id, &|game| {
+        if game.host != name {
+            return Err(Error::NotHost);
+        }
+        if game.status != GameStatus::Waiting {
+            return Err(Error::GameStarted);
+        }
+        game.status = GameStatus::InProgress;
+        Ok(())
+    })?;
+    Ok(game.unwrap())
+}
+
+/// Marks a game as finished once its simulation has ended.
+pub fn finish(storage: &dyn Storage, game_id: GameID) {
+    // the last player may have left as the game ended
+    let _ = storage.update_game(game_id, &|game| {
+        game.status = GameStatus::Finished;
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn create_private(storage: &dyn Storage, host: &str) -> GameInfo {
+        create(storage, host, 4, GameMode::Coop, true, 4).unwrap()
+    }
+
+    #[test]
+    fn new_codes_are_well_formed() {
+        for _ in 0..100 {
+            let code = new_code();
+            let (first, second) = code.split_once('-').unwrap();
+            assert_eq!((first.len(), second.len()), (3, 3), "{code}");
+            assert!(first
+                .bytes()
+                .chain(second.bytes())
+                .all(|c| CODE_ALPHABET.contains(&c)));
+            assert_eq!(normalize_code(&code).as_deref(), Some(code.as_str()));
+        }
+    }
+
+    #[test]
+    fn typed_codes_are_normalized() {
+        for typed in ["K7Q-4MX", "k7q4mx", "K7Q 4MX", " k7q-4Mx "] {
+            assert_eq!(normalize_code(typed).as_deref(), Some("K7Q-4MX"), "{typed}");
+        }
+        for typed in ["", "K7Q-4M", "K7Q-4MXX", "K7Q--"] {
+            assert_eq!(normalize_code(typed), None, "{typed}");
+        }
+    }
+
+    #[test]
+    fn codes_find_their_games() {
+        let storage = MemoryStorage::default();
+        let game = create_private(&storage, "alice");
+
+        let typed = game.code.to_lowercase().replace('-', " ");
+        assert_eq!(find_by_code(&storage, &typed).unwrap(), game.id);
+        assert!(matches!(
+            find_by_code(&storage, "not a code"),
+            Err(Error::GameNotFound)
+        ));
+
+        // private games can only be joined by code
+        assert!(matches!(
+            join(&storage, game.id, "bob", false),
+            Err(Error::GameNotFound)
+        ));
+        join(&storage, game.id, "bob", true).unwrap();
+
+        // once everyone has left, the code is free again
+        leave(&storage, game.id, "alice").unwrap();
+        leave(&storage, game.id, "bob").unwrap();
+        assert!(matches!(
+            find_by_code(&storage, &game.code),
+            Err(Error::GameNotFound)
+        ));
+    }
+
+    #[test]
+    fn colliding_codes_are_retried() {
+        let storage = MemoryStorage::default();
+        let taken = create_private(&storage, "alice");
+
+        let mut codes = vec![
+            "ZZZ-ZZZ".to_string(),
+            taken.code.clone(),
+            taken.code.clone(),
+        ];
+        let game = GameInfo {
+            id: GameID::new(),
+            ..taken.clone()
+        };
+        let game = insert_with_code(&storage, game, || codes.pop().unwrap()).unwrap();
+
+        assert_eq!(game.code, "ZZZ-ZZZ");
+        assert_eq!(find_by_code(&storage, "ZZZ-ZZZ").unwrap(), game.id);
+        assert_eq!(find_by_code(&storage, &taken.code).unwrap(), taken.id);
+    }
+
+    #[test]
+    fn running_out_of_codes_is_an_error() {
+        let storage = MemoryStorage::default();
+        let taken = create_private(&storage, "alice");
+
+        let game = GameInfo {
+            id: GameID::new(),
+            ..taken.clone()
+        };
+        let res = insert_with_code(&storage, game, || taken.code.clone());
+        assert!(matches!(res, Err(Error::Storage)));
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use cosmos_raiders_protocol::GameID;
+use hardlight::{
+    tokio::{self, sync::mpsc::UnboundedReceiver},
+    Topic, TopicNotification,
+};
+use tracing::info;
+
+use crate::{broadcast, config, storage, topic, GAMES};
+
+/// Removes games nobody has been connected to for the configured timeout,
+/// along with their simulations. Games are abandoned when their last player's
+/// connection drops without leaving, or when the server stops with games open.
+///
+/// A game's topic only exists while a player is subscribed to it, so the
+/// topic notifications say when each game loses its last player.
+pub async fn run(mut notifications: UnboundedReceiver<TopicNotification>) {
+    let mut collector = Collector::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(config().sweep_interval));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            Some(notif) = notifications.recv() => collector.notify(notif),
+            _ = interval.tick() => collector.sweep(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Collector {
+    /// The topics of games with players connected.
+    live: HashSet<Topic>,
+    /// When each game without any players connected lost its last one, or
+    /// was first seen without any.
+    abandoned: HashMap<Topic, Instant>,
+}
+
+impl Collector {
+    fn notify(&mut self, notif: TopicNotification) {
+        match notif {
+            TopicNotification::Created(topic) => {
+                self.abandoned.remove(&topic);
+                self.live.insert(topic);
+            }
+            TopicNotification::Removed(topic) => {
+                self.live.remove(&topic);
+                self.abandoned.insert(topic, Instant::now());
+            }
+        }
+    }
+
+    fn sweep(&mut self) {
+        // the storage has already logged why it couldn't be read
+        let Ok(games) = storage().games() else {
+            return;
+        };
+        let timeout = Duration::from_secs(config().abandoned_timeout);
+        let now = Instant::now();
+
+        let mut stored = HashSet::new();
+        let mut expired: Vec<GameID> = Vec::new();
+        for game in games {
+            let topic = topic(game.id);
+            stored.insert(topic.clone());
+            if self.live.contains(&topic) {
+                continue;
+            }
+            let since = *self.abandoned.entry(topic).or_insert(now);
+            if now - since >= timeout {
+                expired.push(game.id);
+            }
+        }
+        // forget games that have been removed since, by this sweep or
+        // otherwise
+        for game_id in &expired {
+            stored.remove(&topic(*game_id));
+        }
+        self.abandoned.retain(|topic, _| stored.contains(topic));
+
+        for game_id in &expired {
+            broadcast::forget(*game_id);
+        }
+        let removed = expired
+            .iter()
+            .filter(|game_id| matches!(storage().remove_game(**game_id), Ok(true)))
+            .count();
+        let stopped = {
+            let mut sims = GAMES.lock().unwrap();
+            let before = sims.len();
+            sims.retain(|game_id, _| !expired.contains(game_id));
+            before - sims.len()
+        };
+        if removed > 0 || stopped > 0 {
+            info!("Swept {removed} abandoned games and stopped {stopped} of their simulations");
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{Datelike, Utc};
+use cosmos_raiders_protocol::{
+    Error, LeaderboardEntry, Period, RankedEntry, ScoreRange, MAX_SCORE_RANGE,
+};
+
+use crate::storage::Storage;
+
+/// Returns the name of the board that is current for `period`, e.g.
+/// `daily-2023-10-01` or `weekly-2023-W39`.
+fn current_board(period: Period) -> String {
+    let today = Utc::now().date_naive();
+    match period {
+        Period::Daily => format!("daily-{}", today.format("%Y-%m-%d")),
+        Period::Weekly => {
+            let week = today.iso_week();
+            format!("weekly-{}-W{:02}", week.year(), week.week())
+        }
+        Period::AllTime => "alltime".to_string(),
+    }
+}
+
+/// Submits a score to every period's board. A board is only changed if the
+/// score beats the player's best on it.
+pub fn submit(storage: &dyn Storage, name: &str, score: u32) -> Result<(), Error> {
+    let entry = LeaderboardEntry {
+        name: name.to_string(),
+        score,
+        timestamp: Utc::now().timestamp_millis() as u64,
+    };
+    for period in Period::ALL {
+        storage.submit_score(&current_board(period), &entry)?;
+    }
+    Ok(())
+}
+
+/// Returns the entries in `range` on the current board for `period`, best
+/// first.
+pub fn top(
+    storage: &dyn Storage,
+    period: Period,
+    range: ScoreRange,
+) -> Result<Vec<RankedEntry>, Error> {
+    let entries = storage.scores(
+        &current_board(period),
+        range.start as usize,
+        range.count.min(MAX_SCORE_RANGE) as usize,
+    )?;
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| RankedEntry {
+            rank: range.start as u64 + i as u64 + 1,
+            entry,
+        })
+        .collect())
+}
+
+/// Returns the player's best entry on the current board for `period`, or
+/// `None` if they haven't submitted a score to it yet.
+pub fn rank(
+    storage: &dyn Storage,
+    period: Period,
+    name: &str,
+) -> Result<Option<RankedEntry>, Error> {
+    let best = storage.best_score(&current_board(period), name)?;
+    Ok(best.map(|(better, entry)| RankedEntry {
+        rank: better + 1,
+        entry,
+    }))
+}
@@ -1,27 +1,87 @@
 #![feature(lazy_cell)]
 
-use std::sync::LazyLock;
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::Duration,
+};
 
-use chrono::Utc;
-use hardlight::{
-    rkyv::{from_bytes, to_bytes},
-    *,
+use cosmos_raiders_protocol::{
+    cr_server, Error, Event, GameID, GameInfo, GameMode, GamePage, GameQuery, Period, RankedEntry,
+    ScoreRange, ServerResult, Snapshot, MAX_SCORE_RANGE, PROTOCOL_VERSION,
 };
-use sled::{transaction::abort, Db};
+use cosmos_raiders_sim::simulation::Simulation;
+use hardlight::*;
 use tracing::info;
 
+use config::{Command, Config, ConfigError};
+use storage::{MemoryStorage, SledStorage, Storage};
+
+mod accounts;
+mod admin;
+mod broadcast;
+mod config;
+mod games;
+mod gc;
+mod leaderboard;
+mod storage;
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let (config, command) = Config::load().unwrap_or_else(|e| exit_with(e));
+    // validated while loading
+    let level = config.log_level().unwrap();
+    // stdout is left for the admin commands' output
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
+
+    if config.dry_run_migrations {
+        match SledStorage::dry_run_migrations(&config.db_path) {
+            Ok(report) => println!("{report}"),
+            Err(e) => exit_with_storage_error(e),
+        }
+        return;
+    }
+
+    let storage: Box<dyn Storage> = if config.in_memory {
+        info!("Keeping everything in memory");
+        Box::new(MemoryStorage::default())
+    } else {
+        match SledStorage::open(&config.db_path) {
+            Ok(storage) => Box::new(storage),
+            Err(e) => exit_with_storage_error(e),
+        }
+    };
+    if STORAGE.set(storage).is_err() {
+        unreachable!("storage opened twice");
+    }
+    CONFIG.set(config).unwrap();
+
+    match command {
+        Command::Serve => serve().await,
+        Command::Admin(command) => {
+            if let Err(e) = admin::run(storage(), command) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
-    let config = ServerConfig::new_self_signed("localhost:8080");
-    let mut server = Server::new(config, factory!(Handler));
-    let _event_emitter = server.get_event_emitter();
+async fn serve() {
+    let server_config = config().server_config().unwrap_or_else(|e| exit_with(e));
+    info!("Listening on {}", config().bind);
+
+    let mut server = Server::new(server_config, factory!(Handler));
+    broadcast::init(server.get_event_emitter());
     let mut topic_notifier = server.get_topic_notifier().unwrap();
 
+    let (gc_tx, gc_rx) = tokio::sync::mpsc::unbounded_channel();
     tokio::spawn(async move {
         while let Some(notif) = topic_notifier.recv().await {
-            match notif {
+            match &notif {
                 TopicNotification::Created(topic) => {
                     info!("Topic created: {:?}", topic);
                 }
@@ -29,131 +89,322 @@ async fn main() {
                     info!("Topic removed: {:?}", topic);
                 }
             }
+            // the collector only stops if the server does
+            let _ = gc_tx.send(notif);
         }
     });
+    tokio::spawn(gc::run(gc_rx));
+
+    tokio::spawn(tick_loop());
 
     server.run().await.unwrap()
 }
 
-static DB: LazyLock<Db> = LazyLock::new(|| {
-    info!("Opening database at cr.db");
-    let db = sled::open("cr.db").unwrap();
-    info!("Database opened");
-    db
-});
-
-#[rpc]
-pub trait CRServer {
-    async fn setup(&self, name: String) -> HandlerResult<Result<(), Error>>;
-    async fn create_game(&self) -> HandlerResult<ServerResult<GameID>>;
-    async fn list_games(&self) -> HandlerResult<Vec<GameID>>;
-    async fn join_game(&self, game_id: GameID) -> HandlerResult<ServerResult<()>>;
-    async fn update_x_position(&self, x: f32) -> HandlerResult<ServerResult<()>>;
-    async fn shoot(&self) -> HandlerResult<ServerResult<()>>;
+/// Reports an invalid config and stops the server.
+fn exit_with(e: ConfigError) -> ! {
+    eprintln!("Invalid config: {e}");
+    std::process::exit(1)
 }
 
-#[connection_state]
-pub struct State {
-    name: Option<String>,
-    game_id: Option<GameID>,
-    current_x: f32,
+/// Reports a database that can't be opened and stops the server.
+fn exit_with_storage_error(e: impl std::fmt::Display) -> ! {
+    eprintln!("Couldn't open the database: {e}");
+    std::process::exit(1)
 }
 
-#[codable]
-#[derive(Debug, Clone, Copy, Default, PartialEq)]
-pub struct GameID([u8; 16]);
+/// Steps every running game once per tick, broadcasting what happened and an
+/// authoritative snapshot on each game's topic.
+async fn tick_loop() {
+    let tick_interval = Duration::from_secs(1) / config().tick_rate;
+    let mut interval = tokio::time::interval(tick_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+
+        // don't hold the lock while emitting, so RPCs aren't blocked on slow
+        // connections
+        let mut outgoing = Vec::new();
+        {
+            let mut sims = GAMES.lock().unwrap();
+            for (game_id, sim) in sims.iter_mut() {
+                let events = sim.step(tick_interval.as_secs_f32());
+                let mut out: Vec<Event> = events.into_iter().map(Event::from).collect();
+                out.push(Event::Snapshot(Snapshot::from(&*sim)));
+                outgoing.push((*game_id, out));
+            }
+            // finished games have sent their last snapshot
+            sims.retain(|game_id, sim| {
+                if sim.game_over {
+                    games::finish(storage(), *game_id);
+                    for ship in &sim.ships {
+                        submit_score(&ship.name, ship.score);
+                    }
+                }
+                !sim.game_over
+            });
+        }
 
-impl GameID {
-    pub fn new() -> Self {
-        Self(rand::random())
+        for (game_id, events) in outgoing {
+            broadcast::emit(game_id, events).await;
+        }
     }
 }
 
-#[rpc_handler]
-impl CRServer for Handler {
-    async fn setup(&self, name: String) -> HandlerResult<Result<(), Error>> {
-        if name.len() > 32 {
-            return Ok(Err(Error::NameTooLong));
-        }
-        if name.len() == 0 {
-            return Ok(Err(Error::NameTooShort));
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The server's config. Only available once `main` has loaded it.
+fn config() -> &'static Config {
+    CONFIG.get().expect("config not loaded")
+}
+
+static STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+/// Where accounts, games and scores are kept. Only available once `main` has
+/// opened it.
+fn storage() -> &'static dyn Storage {
+    STORAGE.get().expect("storage not opened").as_ref()
+}
+
+/// The simulations of every game running on this server.
+static GAMES: LazyLock<Mutex<HashMap<GameID, Simulation>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+cr_server!();
+
+/// The topic a game's events are sent on.
+fn topic(game_id: GameID) -> Topic {
+    game_id.0.to_vec().into()
+}
+
+/// Removes a player from a game and its simulation, ending the simulation if
+/// they were the last player in it, and tells anyone left.
+async fn leave(game_id: GameID, name: &str) {
+    let remaining = match games::leave(storage(), game_id, name) {
+        Ok(game) => game,
+        // the game was already removed
+        Err(_) => None,
+    };
+    let score = {
+        let mut sims = GAMES.lock().unwrap();
+        let score = sims
+            .get(&game_id)
+            .and_then(|sim| sim.ships.iter().find(|ship| ship.name == name))
+            .map(|ship| ship.score);
+        match remaining {
+            Some(_) => {
+                if let Some(sim) = sims.get_mut(&game_id) {
+                    sim.remove_ship(name);
+                }
+            }
+            None => {
+                sims.remove(&game_id);
+            }
         }
-        if name.contains(|c: char| !c.is_ascii_alphanumeric()) {
-            return Ok(Err(Error::NameInvalid));
+        score
+    };
+    info!("{name} left game {game_id:?}");
+    // leaving part way through a game still counts what was scored
+    if let Some(score) = score {
+        submit_score(name, score);
+    }
+
+    match remaining {
+        Some(_) => {
+            let event = Event::PlayerLeft {
+                name: name.to_string(),
+            };
+            broadcast::emit(game_id, vec![event]).await;
         }
+        None => broadcast::forget(game_id),
+    }
+}
 
-        let mut key = b"name-".to_vec();
-        key.extend_from_slice(name.as_bytes());
+/// Puts a score from an online game on the leaderboards. Scores only come from
+/// the server's simulations, so clients can't claim scores they didn't get.
+fn submit_score(name: &str, score: u32) {
+    // players who didn't score anything aren't put on the boards
+    if score == 0 {
+        return;
+    }
+    // the storage has already logged why it couldn't be written
+    let _ = leaderboard::submit(storage(), name, score);
+}
+
+/// Takes a player out of their game when their connection drops.
+impl Drop for Handler {
+    fn drop(&mut self) {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let state = state.read().await;
+            if let (Some(name), Some(game_id)) = (&state.name, state.game_id) {
+                leave(game_id, name).await;
+            }
+        });
+    }
+}
 
-        if DB.contains_key(&key).unwrap() {
-            return Ok(Err(Error::NameTaken));
+#[rpc_handler]
+impl CRServer for Handler {
+    async fn handshake(&self, version: u32) -> HandlerResult<ServerResult<()>> {
+        if version != PROTOCOL_VERSION {
+            info!("Turned away a client speaking protocol version {version}");
+            return Ok(Err(Error::IncompatibleVersion {
+                server: PROTOCOL_VERSION,
+            }));
         }
+        self.state.write().await.compatible = true;
+        Ok(Ok(()))
+    }
 
-        DB.insert(&key, b"").unwrap();
+    async fn register(&self, name: String, password: String) -> HandlerResult<ServerResult<()>> {
+        if let Err(e) = self.check_compatible().await {
+            return Ok(Err(e));
+        }
+        Ok(accounts::register(storage(), &name, &password))
+    }
 
-        Ok(Ok(()))
+    async fn login(&self, name: String, password: String) -> HandlerResult<ServerResult<String>> {
+        if let Err(e) = self.check_compatible().await {
+            return Ok(Err(e));
+        }
+        // held until the name is set, so the connection can't join a game
+        // under its old name in the meantime
+        let mut state = self.state.write().await;
+        // switching accounts mid-game would leave the old name in the game,
+        // where nothing could remove it
+        if state.game_id.is_some() {
+            return Ok(Err(Error::AlreadyInGame));
+        }
+        let token = match accounts::login(storage(), &name, &password) {
+            Ok(token) => token,
+            Err(e) => return Ok(Err(e)),
+        };
+        info!("{name} logged in");
+        state.name = Some(name);
+        Ok(Ok(token))
     }
 
-    async fn create_game(&self) -> HandlerResult<ServerResult<GameID>> {
-        let game_id = GameID::new();
-        let name = match self.state.read().await.name.clone() {
-            Some(name) => name,
-            None => return Ok(Err(Error::NameNotSet)),
+    async fn resume(&self, token: String) -> HandlerResult<ServerResult<String>> {
+        if let Err(e) = self.check_compatible().await {
+            return Ok(Err(e));
+        }
+        // see login
+        let mut state = self.state.write().await;
+        if state.game_id.is_some() {
+            return Ok(Err(Error::AlreadyInGame));
+        }
+        let name = match accounts::resume(storage(), &token) {
+            Ok(name) => name,
+            Err(e) => return Ok(Err(e)),
         };
-        let mut key = b"game-".to_vec();
-        key.extend_from_slice(&game_id.0);
-        let val = to_bytes::<_, 1024>(&vec![name]).unwrap().to_vec();
-        DB.insert(key, val).unwrap();
-        Ok(Ok(game_id))
-    }
-
-    async fn list_games(&self) -> HandlerResult<Vec<GameID>> {
-        Ok(DB
-            .scan_prefix(b"game-")
-            .map(|res| {
-                let (key, _) = res.unwrap();
-                let mut game_id = [0u8; 16];
-                game_id.copy_from_slice(&key[5..]);
-                GameID(game_id)
-            })
-            .collect())
+        info!("{name} resumed their session");
+        state.name = Some(name.clone());
+        Ok(Ok(name))
     }
 
-    async fn join_game(&self, game_id: GameID) -> HandlerResult<ServerResult<()>> {
+    async fn create_game(
+        &self,
+        capacity: u32,
+        mode: GameMode,
+        private: bool,
+    ) -> HandlerResult<ServerResult<GameInfo>> {
         let state = self.state.read().await;
-
         if state.game_id.is_some() {
             return Ok(Err(Error::AlreadyInGame));
         }
-
         let name = match state.name.clone() {
             Some(name) => name,
             None => return Ok(Err(Error::NameNotSet)),
         };
-
         drop(state);
 
-        self.subscriptions.add(&game_id.0.to_vec().into());
+        let game = match games::create(
+            storage(),
+            &name,
+            capacity,
+            mode,
+            private,
+            config().max_players,
+        ) {
+            Ok(game) => game,
+            Err(e) => return Ok(Err(e)),
+        };
+        info!("{name} created game {:?}", game.id);
+        self.subscriptions.add(&topic(game.id));
+        self.state.write().await.game_id = Some(game.id);
+        Ok(Ok(game))
+    }
 
-        match DB.transaction(|tx_db| {
-            let mut key = b"game-".to_vec();
-            key.extend_from_slice(&game_id.0);
+    async fn list_games(&self, query: GameQuery) -> HandlerResult<ServerResult<GamePage>> {
+        if let Err(e) = self.check_logged_in().await {
+            return Ok(Err(e));
+        }
+        Ok(games::list(storage(), query))
+    }
 
-            let mut prev = match tx_db.get(key.clone())? {
-                Some(prev) => from_bytes::<Vec<String>>(&prev).unwrap(),
-                None => abort("prev game does not exist")?,
-            };
+    async fn join_game(&self, game_id: GameID) -> HandlerResult<ServerResult<GameInfo>> {
+        Ok(self.join(game_id, false).await)
+    }
+
+    async fn join_game_by_code(&self, code: String) -> HandlerResult<ServerResult<GameInfo>> {
+        let game_id = match games::find_by_code(storage(), &code) {
+            Ok(game_id) => game_id,
+            Err(e) => return Ok(Err(e)),
+        };
+        Ok(self.join(game_id, true).await)
+    }
 
-            prev.push(name.clone());
+    async fn leave_game(&self) -> HandlerResult<ServerResult<()>> {
+        let mut state = self.state.write().await;
+        let name = match state.name.clone() {
+            Some(name) => name,
+            None => return Ok(Err(Error::NameNotSet)),
+        };
+        let game_id = match state.game_id.take() {
+            Some(game_id) => game_id,
+            None => return Ok(Err(Error::GameNotSet)),
+        };
+        drop(state);
 
-            let val = to_bytes::<_, 1024>(&prev).unwrap().to_vec();
-            tx_db.insert(key, val)?;
+        self.subscriptions.remove(&topic(game_id));
+        leave(game_id, &name).await;
+        Ok(Ok(()))
+    }
 
-            Ok(())
-        }) {
-            Ok(_) => Ok(Ok(())),
-            Err(_) => Ok(Err(Error::GameNotSet)),
+    async fn start_game(&self) -> HandlerResult<ServerResult<()>> {
+        let state = self.state.read().await;
+        let name = match state.name.clone() {
+            Some(name) => name,
+            None => return Ok(Err(Error::NameNotSet)),
+        };
+        let game_id = match state.game_id {
+            Some(game_id) => game_id,
+            None => return Ok(Err(Error::GameNotSet)),
+        };
+        drop(state);
+
+        {
+            // the games are counted and the new one added under one lock, so
+            // games started at the same time can't take the server past
+            // max_games
+            let mut sims = GAMES.lock().unwrap();
+            if sims.len() >= config().max_games {
+                return Ok(Err(Error::ServerFull));
+            }
+            let game = match games::start(storage(), game_id, &name) {
+                Ok(game) => game,
+                Err(e) => return Ok(Err(e)),
+            };
+            let mut sim = Simulation::default();
+            for player in game.players {
+                sim.add_ship(player);
+            }
+            sims.insert(game_id, sim);
         }
+        info!("{name} started game {game_id:?}");
+
+        broadcast::emit(game_id, vec![Event::GameStarted]).await;
+        Ok(Ok(()))
     }
 
     async fn update_x_position(&self, x: f32) -> HandlerResult<ServerResult<()>> {
@@ -168,71 +419,104 @@ impl CRServer for Handler {
         };
         drop(state);
 
-        let key = create_x_pos_key(&name, &game_id);
-
         self.state.write().await.current_x = x;
+        if let Some(sim) = GAMES.lock().unwrap().get_mut(&game_id) {
+            sim.set_ship_x(&name, x);
+        }
 
-        let pos = XPosition::new(x);
-        let val = to_bytes::<_, 1024>(&pos).unwrap().to_vec();
-        DB.insert(key, val).unwrap();
-
+        broadcast::emit(game_id, vec![Event::PlayerMoved { name, x }]).await;
         Ok(Ok(()))
     }
 
     async fn shoot(&self) -> HandlerResult<ServerResult<()>> {
         let state = self.state.read().await;
+        let name = match state.name.clone() {
+            Some(name) => name,
+            None => return Ok(Err(Error::NameNotSet)),
+        };
         let game_id = match state.game_id.clone() {
             Some(game_id) => game_id,
             None => return Ok(Err(Error::GameNotSet)),
         };
-        self.events
-            .emit(
-                &game_id.0.to_vec().into(),
-                Event::Laser { x: state.current_x },
-            )
-            .await;
+        let x = state.current_x;
+        drop(state);
+
+        let fired = match GAMES.lock().unwrap().get_mut(&game_id) {
+            Some(sim) => sim.fire(&name),
+            None => return Ok(Err(Error::GameNotStarted)),
+        };
+        // the player already has a laser on screen
+        if !fired {
+            return Ok(Ok(()));
+        }
+        broadcast::emit(game_id, vec![Event::Laser { by: name, x }]).await;
         Ok(Ok(()))
     }
-}
 
-fn create_x_pos_key(name: &String, game_id: &GameID) -> Vec<u8> {
-    let mut key = b"pos-".to_vec();
-    key.extend_from_slice(name.as_bytes());
-    key.extend_from_slice(b"-");
-    key.extend_from_slice(&game_id.0);
-    key
-}
+    async fn top_scores(
+        &self,
+        period: Period,
+        range: ScoreRange,
+    ) -> HandlerResult<ServerResult<Vec<RankedEntry>>> {
+        if let Err(e) = self.check_logged_in().await {
+            return Ok(Err(e));
+        }
+        if range.count > MAX_SCORE_RANGE {
+            return Ok(Err(Error::RangeTooLarge));
+        }
+        Ok(leaderboard::top(storage(), period, range))
+    }
 
-#[codable]
-#[derive(Debug, Clone)]
-enum Event {
-    Laser { x: f32 },
+    async fn my_rank(&self, period: Period) -> HandlerResult<ServerResult<Option<RankedEntry>>> {
+        let name = match self.state.read().await.name.clone() {
+            Some(name) => name,
+            None => return Ok(Err(Error::NameNotSet)),
+        };
+        Ok(leaderboard::rank(storage(), period, &name))
+    }
 }
 
-#[codable]
-struct XPosition {
-    x: f32,
-    timestamp: u64,
-}
+impl Handler {
+    async fn join(&self, game_id: GameID, by_code: bool) -> ServerResult<GameInfo> {
+        let state = self.state.read().await;
 
-impl XPosition {
-    fn new(x: f32) -> Self {
-        Self {
-            x,
-            timestamp: Utc::now().timestamp_millis() as u64,
+        if state.game_id.is_some() {
+            return Err(Error::AlreadyInGame);
         }
+
+        let name = match state.name.clone() {
+            Some(name) => name,
+            None => return Err(Error::NameNotSet),
+        };
+
+        drop(state);
+
+        let game = games::join(storage(), game_id, &name, by_code)?;
+        self.subscriptions.add(&topic(game_id));
+        self.state.write().await.game_id = Some(game_id);
+        broadcast::emit(game_id, vec![Event::PlayerJoined { name }]).await;
+        Ok(game)
     }
-}
 
-#[codable]
-pub enum Error {
-    NameTaken,
-    NameTooLong,
-    NameTooShort,
-    NameInvalid,
-    NameNotSet,
-    GameNotSet,
-    AlreadyInGame,
-}
+    /// Checks the handshake for RPCs that don't need a login, see
+    /// [`Self::check_logged_in`] for the rest.
+    async fn check_compatible(&self) -> ServerResult<()> {
+        if self.state.read().await.compatible {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleVersion {
+                server: PROTOCOL_VERSION,
+            })
+        }
+    }
 
-pub type ServerResult<T> = Result<T, Error>;
+    /// Checks both the handshake and the login, for RPCs that don't otherwise
+    /// need the player's name.
+    async fn check_logged_in(&self) -> ServerResult<()> {
+        self.check_compatible().await?;
+        if self.state.read().await.name.is_none() {
+            return Err(Error::NameNotSet);
+        }
+        Ok(())
+    }
+}
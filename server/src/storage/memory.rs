@@ -0,0 +1,240 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+
+use cosmos_raiders_protocol::{Error, GameID, GameInfo, GameSummary, LeaderboardEntry};
+
+use super::{index_key, index_range, score_key, Storage};
+use crate::accounts::{Account, Session};
+
+/// Storage that only lasts as long as the server, for testing. Everything is
+/// kept behind one lock, so every operation is atomic.
+#[derive(Default)]
+pub struct MemoryStorage {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    accounts: HashMap<String, Account>,
+    sessions: HashMap<String, Session>,
+    games: HashMap<GameID, GameInfo>,
+    codes: HashMap<String, GameID>,
+    /// The lobby index, keyed the same way as on disk.
+    game_index: BTreeMap<Vec<u8>, GameSummary>,
+    boards: HashMap<String, Board>,
+}
+
+#[derive(Default)]
+struct Board {
+    scores: BTreeMap<Vec<u8>, LeaderboardEntry>,
+    /// Each player's key in `scores`.
+    best: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    fn lock(&self) -> MutexGuard<Inner> {
+        self.inner.lock().unwrap()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn insert_account(&self, name: &str, account: &Account) -> Result<bool, Error> {
+        let mut inner = self.lock();
+        if inner.accounts.contains_key(name) {
+            return Ok(false);
+        }
+        inner.accounts.insert(name.to_string(), account.clone());
+        Ok(true)
+    }
+
+    fn account(&self, name: &str) -> Result<Option<Account>, Error> {
+        Ok(self.lock().accounts.get(name).cloned())
+    }
+
+    fn accounts(&self) -> Result<Vec<(String, Account)>, Error> {
+        let inner = self.lock();
+        Ok(inner
+            .accounts
+            .iter()
+            .map(|(name, account)| (name.clone(), account.clone()))
+            .collect())
+    }
+
+    fn remove_account(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.lock().accounts.remove(name).is_some())
+    }
+
+    fn insert_session(&self, token: &str, session: &Session) -> Result<(), Error> {
+        self.lock()
+            .sessions
+            .insert(token.to_string(), session.clone());
+        Ok(())
+    }
+
+    fn session(&self, token: &str) -> Result<Option<Session>, Error> {
+        Ok(self.lock().sessions.get(token).cloned())
+    }
+
+    fn remove_session(&self, token: &str) -> Result<(), Error> {
+        self.lock().sessions.remove(token);
+        Ok(())
+    }
+
+    fn sessions(&self) -> Result<Vec<(String, Session)>, Error> {
+        let inner = self.lock();
+        Ok(inner
+            .sessions
+            .iter()
+            .map(|(token, session)| (token.clone(), session.clone()))
+            .collect())
+    }
+
+    fn insert_game(&self, game: &GameInfo) -> Result<bool, Error> {
+        let mut inner = self.lock();
+        if inner.codes.contains_key(&game.code) {
+            return Ok(false);
+        }
+        inner.codes.insert(game.code.clone(), game.id);
+        inner.games.insert(game.id, game.clone());
+        if !game.private {
+            inner
+                .game_index
+                .insert(index_key(game), GameSummary::from(game));
+        }
+        Ok(true)
+    }
+
+    fn update_game(
+        &self,
+        game_id: GameID,
+        f: &dyn Fn(&mut GameInfo) -> Result<(), Error>,
+    ) -> Result<Option<GameInfo>, Error> {
+        let mut inner = self.lock();
+        let Some(mut game) = inner.games.get(&game_id).cloned() else {
+            return Err(Error::GameNotFound);
+        };
+        f(&mut game)?;
+        // the status is part of the index key, so the old entry is replaced
+        let old_key = index_key(&inner.games[&game_id]);
+        inner.game_index.remove(&old_key);
+        if game.players.is_empty() {
+            inner.games.remove(&game_id);
+            inner.codes.remove(&game.code);
+            return Ok(None);
+        }
+        if !game.private {
+            inner
+                .game_index
+                .insert(index_key(&game), GameSummary::from(&game));
+        }
+        inner.games.insert(game_id, game.clone());
+        Ok(Some(game))
+    }
+
+    fn remove_game(&self, game_id: GameID) -> Result<bool, Error> {
+        let mut inner = self.lock();
+        let Some(game) = inner.games.remove(&game_id) else {
+            return Ok(false);
+        };
+        inner.codes.remove(&game.code);
+        inner.game_index.remove(&index_key(&game));
+        Ok(true)
+    }
+
+    fn games(&self) -> Result<Vec<GameInfo>, Error> {
+        Ok(self.lock().games.values().cloned().collect())
+    }
+
+    fn game_by_code(&self, code: &str) -> Result<Option<GameID>, Error> {
+        Ok(self.lock().codes.get(code).copied())
+    }
+
+    fn scan_games(
+        &self,
+        cursor: Option<&[u8]>,
+        waiting_only: bool,
+        f: &mut dyn FnMut(&[u8], GameSummary) -> bool,
+    ) -> Result<(), Error> {
+        let inner = self.lock();
+        for (key, summary) in inner.game_index.range(index_range(cursor, waiting_only)) {
+            if !f(key, summary.clone()) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn submit_score(&self, board: &str, entry: &LeaderboardEntry) -> Result<(), Error> {
+        let mut inner = self.lock();
+        let board = inner.boards.entry(board.to_string()).or_default();
+        if let Some(prev) = board.best.get(&entry.name) {
+            if board.scores[prev].score >= entry.score {
+                return Ok(());
+            }
+            board.scores.remove(prev);
+        }
+        let key = score_key(entry);
+        board.scores.insert(key.clone(), entry.clone());
+        board.best.insert(entry.name.clone(), key);
+        Ok(())
+    }
+
+    fn scores(
+        &self,
+        board: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<LeaderboardEntry>, Error> {
+        let inner = self.lock();
+        let Some(board) = inner.boards.get(board) else {
+            return Ok(Vec::new());
+        };
+        Ok(board
+            .scores
+            .values()
+            .skip(start)
+            .take(count)
+            .cloned()
+            .collect())
+    }
+
+    fn best_score(
+        &self,
+        board: &str,
+        name: &str,
+    ) -> Result<Option<(u64, LeaderboardEntry)>, Error> {
+        let inner = self.lock();
+        let Some(board) = inner.boards.get(board) else {
+            return Ok(None);
+        };
+        let Some(key) = board.best.get(name) else {
+            return Ok(None);
+        };
+        // every key before this one is a better score
+        let better = board.scores.range(..key.clone()).count() as u64;
+        Ok(Some((better, board.scores[key].clone())))
+    }
+
+    fn remove_score(&self, board: &str, name: &str) -> Result<bool, Error> {
+        let mut inner = self.lock();
+        let Some(board) = inner.boards.get_mut(board) else {
+            return Ok(false);
+        };
+        let Some(key) = board.best.remove(name) else {
+            return Ok(false);
+        };
+        board.scores.remove(&key);
+        Ok(true)
+    }
+
+    fn boards(&self) -> Result<Vec<String>, Error> {
+        Ok(self.lock().boards.keys().cloned().collect())
+    }
+
+    fn remove_board(&self, board: &str) -> Result<(), Error> {
+        self.lock().boards.remove(board);
+        Ok(())
+    }
+}
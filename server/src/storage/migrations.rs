@@ -0,0 +1,385 @@
+use std::fmt::Display;
+
+use cosmos_raiders_protocol::GameInfo;
+use hardlight::rkyv::from_bytes;
+use sled::Db;
+use tracing::info;
+
+use super::{
+    index_key,
+    sled_storage::{
+        encode_summary, ACCOUNTS_TREE, CODES_TREE, GAMES_TREE, INDEX_TREE, SESSIONS_TREE,
+    },
+};
+
+/// The version of the database layout this server reads and writes. Stored
+/// values have no version of their own, so this has to be bumped, with a
+/// migration added to [`MIGRATIONS`], whenever the layout or a stored type
+/// changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Where the schema version is stored in the default tree. Databases without
+/// one are from before versioning, so are version 0.
+const VERSION_KEY: &[u8] = b"schema-version";
+
+type Migration = fn(&mut Migrator) -> sled::Result<()>;
+
+/// `MIGRATIONS[n]` upgrades a database from version `n` to `n + 1`.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [split_keyspace];
+
+#[derive(Debug)]
+pub enum OpenError {
+    Sled(sled::Error),
+    /// There's no database to dry run the migrations on.
+    Missing,
+    /// The database was written by a newer server than this one.
+    TooNew {
+        version: u32,
+    },
+    /// The stored schema version isn't a big-endian `u32`.
+    BadVersion {
+        len: usize,
+    },
+}
+
+impl From<sled::Error> for OpenError {
+    fn from(e: sled::Error) -> Self {
+        OpenError::Sled(e)
+    }
+}
+
+impl Display for OpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenError::Sled(e) => write!(f, "{e}"),
+            OpenError::Missing => write!(f, "it doesn't exist"),
+            OpenError::TooNew { version } => write!(
+                f,
+                "it's at schema version {version}, but this server only understands up to \
+                 {SCHEMA_VERSION}"
+            ),
+            OpenError::BadVersion { len } => write!(
+                f,
+                "its schema version is {len} bytes long instead of 4, so it may not be a \
+                 Cosmos Raiders database"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OpenError {}
+
+/// What the migrations changed, or would change in a dry run.
+#[derive(Debug)]
+pub struct Report {
+    pub from: u32,
+    pub to: u32,
+    /// How many records each kind of change applied to, in the order they
+    /// were first made.
+    changes: Vec<(&'static str, usize)>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.from == self.to {
+            return write!(f, "The database is at schema version {}", self.to);
+        }
+        write!(f, "Schema version {} to {}", self.from, self.to)?;
+        if self.changes.is_empty() {
+            write!(f, "\n  no records changed")?;
+        }
+        for (change, count) in &self.changes {
+            write!(f, "\n  {change}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies the changes a migration makes, or only counts them in a dry run.
+///
+/// Each change is safe to repeat, and the schema version is only bumped once
+/// a whole migration is done, so a migration that's interrupted part way is
+/// finished the next time the server starts.
+struct Migrator<'a> {
+    db: &'a Db,
+    dry_run: bool,
+    report: Report,
+}
+
+impl Migrator<'_> {
+    fn record(&mut self, change: &'static str) {
+        match self.report.changes.iter_mut().find(|(c, _)| *c == change) {
+            Some((_, count)) => *count += 1,
+            None => self.report.changes.push((change, 1)),
+        }
+    }
+
+    /// Moves a record out of the default tree into `tree`, under `new_key`.
+    fn move_record(
+        &mut self,
+        key: &[u8],
+        val: &[u8],
+        tree: &str,
+        new_key: &[u8],
+        change: &'static str,
+    ) -> sled::Result<()> {
+        if !self.dry_run {
+            self.db.open_tree(tree)?.insert(new_key, val)?;
+            self.db.remove(key)?;
+        }
+        self.record(change);
+        Ok(())
+    }
+
+    /// Removes a record from the default tree.
+    fn remove(&mut self, key: &[u8], change: &'static str) -> sled::Result<()> {
+        if !self.dry_run {
+            self.db.remove(key)?;
+        }
+        self.record(change);
+        Ok(())
+    }
+}
+
+/// Upgrades the database to [`SCHEMA_VERSION`], or in a dry run, reports what
+/// upgrading it would change without changing anything.
+pub fn run(db: &Db, dry_run: bool) -> Result<Report, OpenError> {
+    let from = match db.get(VERSION_KEY)? {
+        Some(val) => {
+            let bytes = val
+                .as_ref()
+                .try_into()
+                .map_err(|_| OpenError::BadVersion { len: val.len() })?;
+            u32::from_be_bytes(bytes)
+        }
+        None => 0,
+    };
+    if from > SCHEMA_VERSION {
+        return Err(OpenError::TooNew { version: from });
+    }
+
+    let mut migrator = Migrator {
+        db,
+        dry_run,
+        report: Report {
+            from,
+            to: SCHEMA_VERSION,
+            changes: Vec::new(),
+        },
+    };
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        let to = version as u32 + 1;
+        if !dry_run {
+            info!("Migrating the database from schema version {version} to {to}");
+        }
+        migration(&mut migrator)?;
+        if !dry_run {
+            db.insert(VERSION_KEY, to.to_be_bytes().to_vec())?;
+            db.flush()?;
+        }
+    }
+    Ok(migrator.report)
+}
+
+/// Version 1: every kind of record moves out of the default tree, where they
+/// were told apart by a key prefix, into its own tree. Ship positions from the
+/// first servers are dropped, as they're no longer stored, and so are game
+/// records from before games had metadata, which can't be read. The lobby
+/// index is rebuilt from the games that are left.
+fn split_keyspace(m: &mut Migrator) -> sled::Result<()> {
+    if !m.dry_run {
+        m.db.open_tree(INDEX_TREE)?.clear()?;
+    }
+    for res in m.db.iter() {
+        let (key, val) = res?;
+        if let Some(name) = key.strip_prefix(b"name-") {
+            // names reserved before accounts had passwords are moved as they
+            // are, so they stay taken
+            m.move_record(&key, &val, ACCOUNTS_TREE, name, "accounts moved")?;
+        } else if let Some(token) = key.strip_prefix(b"session-") {
+            m.move_record(&key, &val, SESSIONS_TREE, token, "sessions moved")?;
+        } else if let Some(code) = key.strip_prefix(b"code-") {
+            m.move_record(&key, &val, CODES_TREE, code, "join codes moved")?;
+        } else if let Some(id) = key.strip_prefix(b"game-") {
+            let Ok(game) = from_bytes::<GameInfo>(&val) else {
+                m.remove(&key, "unreadable games removed")?;
+                continue;
+            };
+            m.move_record(&key, &val, GAMES_TREE, id, "games moved")?;
+            if !game.private {
+                if !m.dry_run {
+                    m.db.open_tree(INDEX_TREE)?
+                        .insert(index_key(&game), encode_summary(&game))?;
+                }
+                m.record("games indexed");
+            }
+        } else if key.starts_with(b"pos-") {
+            m.remove(&key, "positions removed")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use hardlight::rkyv::to_bytes;
+
+    use super::*;
+    use crate::{
+        accounts::{Account, Session},
+        storage::{test_game, SledStorage},
+    };
+
+    /// Fills the default tree the way the servers before schema versioning
+    /// did, returning the public and private games in it.
+    fn write_v0(db: &Db) -> (GameInfo, GameInfo) {
+        let account = Account {
+            password_hash: "$argon2id$hash".to_string(),
+            created: 1,
+        };
+        let session = Session {
+            name: "alice".to_string(),
+            expires: 2,
+        };
+        let public = test_game("AAA-AAA", 1, false);
+        let private = test_game("BBB-BBB", 1, true);
+
+        db.insert(
+            b"name-alice",
+            to_bytes::<_, 1024>(&account).unwrap().to_vec(),
+        )
+        .unwrap();
+        // a name reserved before accounts had passwords
+        db.insert(b"name-bob", &[][..]).unwrap();
+        db.insert(
+            b"session-tok",
+            to_bytes::<_, 1024>(&session).unwrap().to_vec(),
+        )
+        .unwrap();
+        for game in [&public, &private] {
+            let mut key = b"game-".to_vec();
+            key.extend_from_slice(&game.id.0);
+            db.insert(key, to_bytes::<_, 1024>(game).unwrap().to_vec())
+                .unwrap();
+            db.insert(format!("code-{}", game.code), game.id.0.as_slice())
+                .unwrap();
+        }
+        // a game from before games had metadata
+        db.insert(b"game-old", &b"\x01\x02"[..]).unwrap();
+        db.insert(b"pos-alice", &0f32.to_be_bytes()[..]).unwrap();
+        db.flush().unwrap();
+        (public, private)
+    }
+
+    fn count(report: &Report, change: &str) -> usize {
+        report
+            .changes
+            .iter()
+            .find(|(c, _)| *c == change)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    #[test]
+    fn split_keyspace_moves_v0_records() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let (public, private) = write_v0(&db);
+
+        let report = run(&db, false).unwrap();
+        assert_eq!((report.from, report.to), (0, SCHEMA_VERSION));
+        assert_eq!(count(&report, "accounts moved"), 2);
+        assert_eq!(count(&report, "sessions moved"), 1);
+        assert_eq!(count(&report, "join codes moved"), 2);
+        assert_eq!(count(&report, "games moved"), 2);
+        assert_eq!(count(&report, "games indexed"), 1);
+        assert_eq!(count(&report, "unreadable games removed"), 1);
+        assert_eq!(count(&report, "positions removed"), 1);
+
+        // only the schema version is left in the default tree
+        let keys: Vec<_> = db.iter().keys().map(|key| key.unwrap().to_vec()).collect();
+        assert_eq!(keys, vec![VERSION_KEY.to_vec()]);
+
+        let accounts = db.open_tree(ACCOUNTS_TREE).unwrap();
+        assert!(accounts.contains_key("alice").unwrap());
+        assert!(accounts.contains_key("bob").unwrap());
+        let sessions = db.open_tree(SESSIONS_TREE).unwrap();
+        assert!(sessions.contains_key("tok").unwrap());
+        let codes = db.open_tree(CODES_TREE).unwrap();
+        let code = codes.get("BBB-BBB").unwrap().unwrap();
+        assert_eq!(code.to_vec(), private.id.0.to_vec());
+        let games = db.open_tree(GAMES_TREE).unwrap();
+        assert!(games.contains_key(public.id.0).unwrap());
+        assert!(games.contains_key(private.id.0).unwrap());
+        let index = db.open_tree(INDEX_TREE).unwrap();
+        let indexed: Vec<_> = index
+            .iter()
+            .keys()
+            .map(|key| key.unwrap().to_vec())
+            .collect();
+        assert_eq!(indexed, vec![index_key(&public)]);
+
+        // running again finds nothing to do
+        let report = run(&db, false).unwrap();
+        assert_eq!(report.from, SCHEMA_VERSION);
+        assert!(report.changes.is_empty());
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let path = std::env::temp_dir().join(format!("dry-run-migrations-{}", std::process::id()));
+        let before = {
+            let db = sled::open(&path).unwrap();
+            write_v0(&db);
+            snapshot(&db)
+        };
+
+        let report = SledStorage::dry_run_migrations(&path).unwrap();
+        assert_eq!((report.from, report.to), (0, SCHEMA_VERSION));
+        assert_eq!(count(&report, "games moved"), 2);
+
+        let after = snapshot(&sled::open(&path).unwrap());
+        fs::remove_dir_all(&path).unwrap();
+        assert_eq!(before, after);
+    }
+
+    /// Every non-empty tree in a database, with everything in it.
+    fn snapshot(db: &Db) -> Vec<(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>)> {
+        let mut names = db.tree_names();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                let tree = db.open_tree(&name).unwrap();
+                let records = tree
+                    .iter()
+                    .map(|res| {
+                        let (key, val) = res.unwrap();
+                        (key.to_vec(), val.to_vec())
+                    })
+                    .collect::<Vec<_>>();
+                (name.to_vec(), records)
+            })
+            .filter(|(_, records)| !records.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn dry_run_needs_a_database() {
+        let path = PathBuf::from("/nonexistent/cosmos-raiders-db");
+        assert!(matches!(
+            SledStorage::dry_run_migrations(&path),
+            Err(OpenError::Missing)
+        ));
+    }
+
+    #[test]
+    fn malformed_version_is_an_error() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        db.insert(VERSION_KEY, &[1, 2][..]).unwrap();
+        assert!(matches!(
+            run(&db, false),
+            Err(OpenError::BadVersion { len: 2 })
+        ));
+    }
+}
@@ -0,0 +1,349 @@
+use std::{fmt::Display, ops::Bound};
+
+use cosmos_raiders_protocol::{Error, GameID, GameInfo, GameStatus, GameSummary, LeaderboardEntry};
+use tracing::error;
+
+use crate::accounts::{Account, Session};
+
+mod memory;
+mod migrations;
+mod sled_storage;
+
+pub use memory::MemoryStorage;
+pub use sled_storage::SledStorage;
+
+/// Everything the server keeps between connections: accounts and their
+/// sessions, games and their join codes, and the leaderboards.
+///
+/// Ship positions aren't here, as they only live in the running simulations.
+pub trait Storage: Send + Sync {
+    /// Stores a new account, returning `false` if the name is already taken.
+    fn insert_account(&self, name: &str, account: &Account) -> Result<bool, Error>;
+    fn account(&self, name: &str) -> Result<Option<Account>, Error>;
+    /// Returns every account with its name.
+    fn accounts(&self) -> Result<Vec<(String, Account)>, Error>;
+    /// Removes an account, returning `false` if there wasn't one.
+    fn remove_account(&self, name: &str) -> Result<bool, Error>;
+
+    fn insert_session(&self, token: &str, session: &Session) -> Result<(), Error>;
+    fn session(&self, token: &str) -> Result<Option<Session>, Error>;
+    fn remove_session(&self, token: &str) -> Result<(), Error>;
+    /// Returns every session with its token.
+    fn sessions(&self) -> Result<Vec<(String, Session)>, Error>;
+
+    /// Stores a new game, returning `false` without storing it if its join
+    /// code is already in use. Private games are left out of the lobby index.
+    fn insert_game(&self, game: &GameInfo) -> Result<bool, Error>;
+    /// Applies `f` to a game's record atomically, so concurrent joins and
+    /// leaves can't overwrite each other. Games with no players left are
+    /// removed, in which case `None` is returned.
+    fn update_game(
+        &self,
+        game_id: GameID,
+        f: &dyn Fn(&mut GameInfo) -> Result<(), Error>,
+    ) -> Result<Option<GameInfo>, Error>;
+    /// Removes a game whatever state it's in, returning `false` if it was
+    /// already gone.
+    fn remove_game(&self, game_id: GameID) -> Result<bool, Error>;
+    /// Returns every game, including private ones.
+    fn games(&self) -> Result<Vec<GameInfo>, Error>;
+    fn game_by_code(&self, code: &str) -> Result<Option<GameID>, Error>;
+    /// Calls `f` with each lobby index entry after `cursor` in index order,
+    /// until it returns `false`. With `waiting_only`, only the waiting games
+    /// are read.
+    fn scan_games(
+        &self,
+        cursor: Option<&[u8]>,
+        waiting_only: bool,
+        f: &mut dyn FnMut(&[u8], GameSummary) -> bool,
+    ) -> Result<(), Error>;
+
+    /// Puts `entry` on `board`, if it beats the player's best score there.
+    fn submit_score(&self, board: &str, entry: &LeaderboardEntry) -> Result<(), Error>;
+    /// Returns up to `count` entries from `board`, best first, skipping the
+    /// first `start`.
+    fn scores(
+        &self,
+        board: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<LeaderboardEntry>, Error>;
+    /// Returns a player's best entry on `board`, along with how many entries
+    /// beat it.
+    fn best_score(&self, board: &str, name: &str)
+        -> Result<Option<(u64, LeaderboardEntry)>, Error>;
+    /// Takes a player off `board`, returning `false` if they weren't on it.
+    fn remove_score(&self, board: &str, name: &str) -> Result<bool, Error>;
+    /// Returns the name of every board with scores on it, including ones that
+    /// are no longer current.
+    fn boards(&self) -> Result<Vec<String>, Error>;
+    fn remove_board(&self, board: &str) -> Result<(), Error>;
+}
+
+/// Logs an error from the storage backend. Clients are only told that
+/// something went wrong, not what.
+fn storage_error(e: impl Display) -> Error {
+    error!("Storage error: {e}");
+    Error::Storage
+}
+
+fn status_byte(status: GameStatus) -> u8 {
+    match status {
+        GameStatus::Waiting => 0,
+        GameStatus::InProgress => 1,
+        GameStatus::Finished => 2,
+    }
+}
+
+/// The lobby index maps `(status, inverted creation time, id)` to the game's
+/// [`GameSummary`], so iterating it in key order lists waiting games first and
+/// newest first within each status, without reading the records themselves.
+fn index_key(game: &GameInfo) -> Vec<u8> {
+    let mut key = vec![status_byte(game.status)];
+    key.extend_from_slice(&(u64::MAX - game.created).to_be_bytes());
+    key.extend_from_slice(&game.id.0);
+    key
+}
+
+/// The part of the lobby index [`Storage::scan_games`] reads.
+fn index_range(cursor: Option<&[u8]>, waiting_only: bool) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    let start = match cursor {
+        Some(cursor) => Bound::Excluded(cursor.to_vec()),
+        None => Bound::Unbounded,
+    };
+    let end = if waiting_only {
+        Bound::Excluded(vec![status_byte(GameStatus::Waiting) + 1])
+    } else {
+        Bound::Unbounded
+    };
+    (start, end)
+}
+
+/// Leaderboards are keyed by `(inverted score, timestamp, name)`, so iterating
+/// one in key order goes from the best score to the worst, with ties going to
+/// whoever got there first.
+fn score_key(entry: &LeaderboardEntry) -> Vec<u8> {
+    let mut key = (u32::MAX - entry.score).to_be_bytes().to_vec();
+    key.extend_from_slice(&entry.timestamp.to_be_bytes());
+    key.extend_from_slice(entry.name.as_bytes());
+    key
+}
+
+/// Returns the score stored in a key made by [`score_key`].
+fn score_from_key(key: &[u8]) -> u32 {
+    u32::MAX - u32::from_be_bytes(key[..4].try_into().unwrap())
+}
+
+/// A waiting game hosted by alice, who's its only player, for tests.
+#[cfg(test)]
+fn test_game(code: &str, created: u64, private: bool) -> GameInfo {
+    GameInfo {
+        id: GameID::new(),
+        host: "alice".to_string(),
+        players: vec!["alice".to_string()],
+        capacity: 4,
+        mode: cosmos_raiders_protocol::GameMode::Coop,
+        status: GameStatus::Waiting,
+        created,
+        private,
+        code: code.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every backend, so each test checks they behave the same.
+    fn backends() -> Vec<(&'static str, Box<dyn Storage>)> {
+        vec![
+            ("memory", Box::new(MemoryStorage::default())),
+            ("sled", Box::new(SledStorage::temporary())),
+        ]
+    }
+
+    fn entry(name: &str, score: u32, timestamp: u64) -> LeaderboardEntry {
+        LeaderboardEntry {
+            name: name.to_string(),
+            score,
+            timestamp,
+        }
+    }
+
+    fn lobby(storage: &dyn Storage, waiting_only: bool) -> Vec<GameID> {
+        let mut ids = Vec::new();
+        storage
+            .scan_games(None, waiting_only, &mut |_, summary| {
+                ids.push(summary.id);
+                true
+            })
+            .unwrap();
+        ids
+    }
+
+    #[test]
+    fn accounts_round_trip() {
+        for (backend, storage) in backends() {
+            let account = Account {
+                password_hash: "$argon2id$hash".to_string(),
+                created: 1234,
+            };
+            assert!(
+                storage.insert_account("alice", &account).unwrap(),
+                "{backend}"
+            );
+            assert!(
+                !storage.insert_account("alice", &account).unwrap(),
+                "{backend}"
+            );
+
+            let stored = storage.account("alice").unwrap().unwrap();
+            assert_eq!(stored.password_hash, account.password_hash, "{backend}");
+            assert_eq!(stored.created, 1234, "{backend}");
+            assert_eq!(storage.accounts().unwrap().len(), 1, "{backend}");
+
+            assert!(storage.remove_account("alice").unwrap(), "{backend}");
+            assert!(!storage.remove_account("alice").unwrap(), "{backend}");
+            assert!(storage.account("alice").unwrap().is_none(), "{backend}");
+        }
+    }
+
+    #[test]
+    fn sessions_round_trip() {
+        for (backend, storage) in backends() {
+            let session = Session {
+                name: "alice".to_string(),
+                expires: 5678,
+            };
+            storage.insert_session("token", &session).unwrap();
+
+            let stored = storage.session("token").unwrap().unwrap();
+            assert_eq!(stored.name, "alice", "{backend}");
+            assert_eq!(stored.expires, 5678, "{backend}");
+            assert_eq!(storage.sessions().unwrap().len(), 1, "{backend}");
+
+            storage.remove_session("token").unwrap();
+            assert!(storage.session("token").unwrap().is_none(), "{backend}");
+        }
+    }
+
+    #[test]
+    fn games_round_trip() {
+        for (backend, storage) in backends() {
+            let old = test_game("AAA-AAA", 1, false);
+            let new = test_game("BBB-BBB", 2, false);
+            let private = test_game("CCC-CCC", 3, true);
+            for game in [&old, &new, &private] {
+                assert!(storage.insert_game(game).unwrap(), "{backend}");
+            }
+            // a join code can only be used once
+            assert!(
+                !storage
+                    .insert_game(&test_game("AAA-AAA", 4, false))
+                    .unwrap(),
+                "{backend}"
+            );
+
+            assert_eq!(storage.games().unwrap().len(), 3, "{backend}");
+            assert_eq!(
+                storage.game_by_code("CCC-CCC").unwrap(),
+                Some(private.id),
+                "{backend}"
+            );
+            assert_eq!(storage.game_by_code("DDD-DDD").unwrap(), None, "{backend}");
+            // newest first, without the private game
+            assert_eq!(lobby(&*storage, false), vec![new.id, old.id], "{backend}");
+
+            let updated = storage
+                .update_game(old.id, &|game| {
+                    game.players.push("bob".to_string());
+                    game.status = GameStatus::InProgress;
+                    Ok(())
+                })
+                .unwrap()
+                .unwrap();
+            assert_eq!(updated.players, vec!["alice", "bob"], "{backend}");
+            // started games sort after waiting ones, and aren't waiting
+            assert_eq!(lobby(&*storage, false), vec![new.id, old.id], "{backend}");
+            assert_eq!(lobby(&*storage, true), vec![new.id], "{backend}");
+
+            // a failed update changes nothing
+            let res = storage.update_game(new.id, &|game| {
+                game.players.clear();
+                Err(Error::GameFull)
+            });
+            assert!(matches!(res, Err(Error::GameFull)), "{backend}");
+            assert_eq!(lobby(&*storage, true), vec![new.id], "{backend}");
+
+            // games nobody is in are removed, along with their codes
+            let emptied = storage
+                .update_game(new.id, &|game| {
+                    game.players.clear();
+                    Ok(())
+                })
+                .unwrap();
+            assert!(emptied.is_none(), "{backend}");
+            assert_eq!(storage.game_by_code("BBB-BBB").unwrap(), None, "{backend}");
+            assert!(
+                matches!(
+                    storage.update_game(new.id, &|_| Ok(())),
+                    Err(Error::GameNotFound)
+                ),
+                "{backend}"
+            );
+
+            assert!(storage.remove_game(old.id).unwrap(), "{backend}");
+            assert!(!storage.remove_game(old.id).unwrap(), "{backend}");
+            assert_eq!(storage.game_by_code("AAA-AAA").unwrap(), None, "{backend}");
+            assert!(lobby(&*storage, false).is_empty(), "{backend}");
+            assert_eq!(storage.games().unwrap().len(), 1, "{backend}");
+        }
+    }
+
+    #[test]
+    fn scores_round_trip() {
+        for (backend, storage) in backends() {
+            storage
+                .submit_score("all", &entry("alice", 100, 1))
+                .unwrap();
+            storage.submit_score("all", &entry("bob", 300, 2)).unwrap();
+            storage
+                .submit_score("all", &entry("carol", 100, 0))
+                .unwrap();
+            // only a player's best score is kept
+            storage.submit_score("all", &entry("alice", 50, 3)).unwrap();
+            storage
+                .submit_score("all", &entry("alice", 200, 4))
+                .unwrap();
+            storage
+                .submit_score("week", &entry("alice", 10, 5))
+                .unwrap();
+
+            let names = |board: &str| -> Vec<String> {
+                let scores = storage.scores(board, 0, usize::MAX).unwrap();
+                scores.into_iter().map(|entry| entry.name).collect()
+            };
+            assert_eq!(names("all"), vec!["bob", "alice", "carol"], "{backend}");
+            let page = storage.scores("all", 1, 1).unwrap();
+            assert_eq!(page[0].name, "alice", "{backend}");
+
+            let (better, best) = storage.best_score("all", "alice").unwrap().unwrap();
+            assert_eq!((better, best.score), (1, 200), "{backend}");
+            assert!(
+                storage.best_score("all", "dave").unwrap().is_none(),
+                "{backend}"
+            );
+
+            let mut boards = storage.boards().unwrap();
+            boards.sort();
+            assert_eq!(boards, vec!["all", "week"], "{backend}");
+
+            assert!(storage.remove_score("all", "bob").unwrap(), "{backend}");
+            assert!(!storage.remove_score("all", "bob").unwrap(), "{backend}");
+            assert_eq!(names("all"), vec!["alice", "carol"], "{backend}");
+
+            storage.remove_board("week").unwrap();
+            assert!(names("week").is_empty(), "{backend}");
+        }
+    }
+}
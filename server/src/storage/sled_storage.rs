@@ -0,0 +1,409 @@
+use std::path::Path;
+
+use cosmos_raiders_protocol::{Error, GameID, GameInfo, GameSummary, LeaderboardEntry};
+use hardlight::rkyv::{from_bytes, to_bytes};
+use sled::{
+    transaction::{abort, TransactionError},
+    Db, Transactional, Tree,
+};
+use tracing::info;
+
+use super::{
+    index_key, index_range,
+    migrations::{self, OpenError, Report},
+    score_from_key, score_key, storage_error, Storage,
+};
+use crate::accounts::{Account, Session};
+
+pub(super) const ACCOUNTS_TREE: &str = "accounts";
+pub(super) const SESSIONS_TREE: &str = "sessions";
+pub(super) const GAMES_TREE: &str = "games";
+pub(super) const CODES_TREE: &str = "codes";
+pub(super) const INDEX_TREE: &str = "game-index";
+
+/// Storage in a sled database on disk. Each kind of record has its own tree:
+///
+/// - `accounts` maps names to accounts.
+/// - `sessions` maps tokens to sessions.
+/// - `games` maps game IDs to games.
+/// - `codes` maps join codes to game IDs.
+/// - `game-index` is the lobby index.
+///
+/// Each leaderboard is made of two more trees:
+///
+/// - `scores-<board>` maps score keys to the entries.
+/// - `best-<board>` maps a player's name to their key in `scores-<board>`, so
+///   each player only has their best score on the board.
+///
+/// The default tree only holds the schema version, see [`migrations`].
+pub struct SledStorage {
+    accounts: Tree,
+    sessions: Tree,
+    games: Tree,
+    codes: Tree,
+    game_index: Tree,
+    db: Db,
+}
+
+pub(super) fn encode_summary(game: &GameInfo) -> Vec<u8> {
+    to_bytes::<_, 1024>(&GameSummary::from(game))
+        .unwrap()
+        .to_vec()
+}
+
+fn decode_entry(val: &[u8]) -> Result<LeaderboardEntry, Error> {
+    from_bytes::<LeaderboardEntry>(val).map_err(storage_error)
+}
+
+impl SledStorage {
+    /// Opens the database at `path`, upgrading it to the current schema first
+    /// if it was written by an older server.
+    pub fn open(path: &Path) -> Result<Self, OpenError> {
+        info!("Opening database at {}", path.display());
+        Self::from_db(sled::open(path)?)
+    }
+
+    fn from_db(db: Db) -> Result<Self, OpenError> {
+        let report = migrations::run(&db, false)?;
+        if report.from != report.to {
+            info!("Migrated the database\n{report}");
+        }
+        let storage = Self {
+            accounts: db.open_tree(ACCOUNTS_TREE)?,
+            sessions: db.open_tree(SESSIONS_TREE)?,
+            games: db.open_tree(GAMES_TREE)?,
+            codes: db.open_tree(CODES_TREE)?,
+            game_index: db.open_tree(INDEX_TREE)?,
+            db,
+        };
+        info!("Database opened");
+        Ok(storage)
+    }
+
+    /// Opens a database that's deleted when it's dropped.
+    #[cfg(test)]
+    pub(super) fn temporary() -> Self {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        Self::from_db(db).unwrap()
+    }
+
+    /// Reports how opening the database at `path` would change it, without
+    /// changing anything.
+    pub fn dry_run_migrations(path: &Path) -> Result<Report, OpenError> {
+        if !path.exists() {
+            return Err(OpenError::Missing);
+        }
+        let db = sled::open(path)?;
+        migrations::run(&db, true)
+    }
+
+    fn board(&self, board: &str) -> Result<(Tree, Tree), Error> {
+        let scores = self
+            .db
+            .open_tree(format!("scores-{board}"))
+            .map_err(storage_error)?;
+        let best = self
+            .db
+            .open_tree(format!("best-{board}"))
+            .map_err(storage_error)?;
+        Ok((scores, best))
+    }
+}
+
+impl Storage for SledStorage {
+    fn insert_account(&self, name: &str, account: &Account) -> Result<bool, Error> {
+        let val = to_bytes::<_, 1024>(account).unwrap().to_vec();
+        // only succeeds if nobody else has the name, even if they're
+        // registering it at the same time
+        let res = self
+            .accounts
+            .compare_and_swap(name, None as Option<&[u8]>, Some(val))
+            .map_err(storage_error)?;
+        Ok(res.is_ok())
+    }
+
+    fn account(&self, name: &str) -> Result<Option<Account>, Error> {
+        let val = self.accounts.get(name).map_err(storage_error)?;
+        // names reserved before accounts had passwords have no account
+        Ok(val.and_then(|val| from_bytes::<Account>(&val).ok()))
+    }
+
+    fn accounts(&self) -> Result<Vec<(String, Account)>, Error> {
+        let mut accounts = Vec::new();
+        for res in self.accounts.iter() {
+            let (name, val) = res.map_err(storage_error)?;
+            // names reserved before accounts had passwords have no account
+            if let Ok(account) = from_bytes::<Account>(&val) {
+                accounts.push((String::from_utf8_lossy(&name).into_owned(), account));
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn remove_account(&self, name: &str) -> Result<bool, Error> {
+        let prev = self.accounts.remove(name).map_err(storage_error)?;
+        Ok(prev.is_some())
+    }
+
+    fn insert_session(&self, token: &str, session: &Session) -> Result<(), Error> {
+        let val = to_bytes::<_, 1024>(session).unwrap().to_vec();
+        self.sessions.insert(token, val).map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn session(&self, token: &str) -> Result<Option<Session>, Error> {
+        let Some(val) = self.sessions.get(token).map_err(storage_error)? else {
+            return Ok(None);
+        };
+        from_bytes::<Session>(&val).map(Some).map_err(storage_error)
+    }
+
+    fn remove_session(&self, token: &str) -> Result<(), Error> {
+        self.sessions.remove(token).map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn sessions(&self) -> Result<Vec<(String, Session)>, Error> {
+        self.sessions
+            .iter()
+            .map(|res| {
+                let (token, val) = res.map_err(storage_error)?;
+                let session = from_bytes::<Session>(&val).map_err(storage_error)?;
+                Ok((String::from_utf8_lossy(&token).into_owned(), session))
+            })
+            .collect()
+    }
+
+    fn insert_game(&self, game: &GameInfo) -> Result<bool, Error> {
+        let val = to_bytes::<_, 1024>(game).unwrap().to_vec();
+        let res =
+            (&self.games, &self.codes, &self.game_index).transaction(|(games, codes, index)| {
+                if codes.get(game.code.as_str())?.is_some() {
+                    return abort(());
+                }
+                codes.insert(game.code.as_str(), game.id.0.as_slice())?;
+                games.insert(game.id.0.as_slice(), val.as_slice())?;
+                if !game.private {
+                    index.insert(index_key(game), encode_summary(game))?;
+                }
+                Ok(())
+            });
+        match res {
+            Ok(()) => Ok(true),
+            Err(TransactionError::Abort(())) => Ok(false),
+            Err(TransactionError::Storage(e)) => Err(storage_error(e)),
+        }
+    }
+
+    fn update_game(
+        &self,
+        game_id: GameID,
+        f: &dyn Fn(&mut GameInfo) -> Result<(), Error>,
+    ) -> Result<Option<GameInfo>, Error> {
+        let key = game_id.0.as_slice();
+        let res =
+            (&self.games, &self.codes, &self.game_index).transaction(|(games, codes, index)| {
+                let Some(val) = games.get(key)? else {
+                    return abort(Error::GameNotFound);
+                };
+                let mut game = match from_bytes::<GameInfo>(&val) {
+                    Ok(game) => game,
+                    Err(e) => return abort(storage_error(e)),
+                };
+                // the status is part of the index key, so the old entry is
+                // replaced
+                index.remove(index_key(&game))?;
+                if let Err(e) = f(&mut game) {
+                    return abort(e);
+                }
+                if game.players.is_empty() {
+                    games.remove(key)?;
+                    codes.remove(game.code.as_str())?;
+                    return Ok(None);
+                }
+                let val = to_bytes::<_, 1024>(&game).unwrap().to_vec();
+                games.insert(key, val)?;
+                if !game.private {
+                    index.insert(index_key(&game), encode_summary(&game))?;
+                }
+                Ok(Some(game))
+            });
+        match res {
+            Ok(game) => Ok(game),
+            Err(TransactionError::Abort(e)) => Err(e),
+            Err(TransactionError::Storage(e)) => Err(storage_error(e)),
+        }
+    }
+
+    fn remove_game(&self, game_id: GameID) -> Result<bool, Error> {
+        let key = game_id.0.as_slice();
+        let res =
+            (&self.games, &self.codes, &self.game_index).transaction(|(games, codes, index)| {
+                let Some(val) = games.remove(key)? else {
+                    return Ok(false);
+                };
+                // the record's still removed if it can't be read
+                if let Ok(game) = from_bytes::<GameInfo>(&val) {
+                    codes.remove(game.code.as_str())?;
+                    index.remove(index_key(&game))?;
+                }
+                Ok(true)
+            });
+        match res {
+            Ok(removed) => Ok(removed),
+            Err(TransactionError::Storage(e)) => Err(storage_error(e)),
+            // nothing in the transaction aborts
+            Err(TransactionError::Abort(())) => unreachable!(),
+        }
+    }
+
+    fn games(&self) -> Result<Vec<GameInfo>, Error> {
+        self.games
+            .iter()
+            .map(|res| {
+                let (_, val) = res.map_err(storage_error)?;
+                from_bytes::<GameInfo>(&val).map_err(storage_error)
+            })
+            .collect()
+    }
+
+    fn game_by_code(&self, code: &str) -> Result<Option<GameID>, Error> {
+        let Some(val) = self.codes.get(code).map_err(storage_error)? else {
+            return Ok(None);
+        };
+        let game_id = <[u8; 16]>::try_from(&*val).map_err(|_| {
+            storage_error(format!(
+                "join code {code} maps to a {} byte game ID",
+                val.len()
+            ))
+        })?;
+        Ok(Some(GameID(game_id)))
+    }
+
+    fn scan_games(
+        &self,
+        cursor: Option<&[u8]>,
+        waiting_only: bool,
+        f: &mut dyn FnMut(&[u8], GameSummary) -> bool,
+    ) -> Result<(), Error> {
+        for res in self
+            .game_index
+            .range::<Vec<u8>, _>(index_range(cursor, waiting_only))
+        {
+            let (key, val) = res.map_err(storage_error)?;
+            let summary = from_bytes::<GameSummary>(&val).map_err(storage_error)?;
+            if !f(&key, summary) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn submit_score(&self, board: &str, entry: &LeaderboardEntry) -> Result<(), Error> {
+        let (scores, best) = self.board(board)?;
+        let key = score_key(entry);
+        let val = to_bytes::<_, 1024>(entry).unwrap().to_vec();
+        let name = entry.name.as_bytes();
+        let res: Result<(), TransactionError> = (&scores, &best).transaction(|(scores, best)| {
+            if let Some(prev) = best.get(name)? {
+                if score_from_key(&prev) >= entry.score {
+                    return Ok(());
+                }
+                scores.remove(prev)?;
+            }
+            scores.insert(key.as_slice(), val.as_slice())?;
+            best.insert(name, key.as_slice())?;
+            Ok(())
+        });
+        match res {
+            Ok(()) => Ok(()),
+            Err(TransactionError::Storage(e)) => Err(storage_error(e)),
+            // nothing in the transaction aborts
+            Err(TransactionError::Abort(())) => unreachable!(),
+        }
+    }
+
+    fn scores(
+        &self,
+        board: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<LeaderboardEntry>, Error> {
+        let (scores, _) = self.board(board)?;
+        scores
+            .iter()
+            .skip(start)
+            .take(count)
+            .map(|res| {
+                let (_, val) = res.map_err(storage_error)?;
+                decode_entry(&val)
+            })
+            .collect()
+    }
+
+    fn best_score(
+        &self,
+        board: &str,
+        name: &str,
+    ) -> Result<Option<(u64, LeaderboardEntry)>, Error> {
+        let (scores, best) = self.board(board)?;
+        let Some(key) = best.get(name.as_bytes()).map_err(storage_error)? else {
+            return Ok(None);
+        };
+        let Some(val) = scores.get(&key).map_err(storage_error)? else {
+            return Ok(None);
+        };
+        // every key before this one is a better score
+        let better = scores.range(..key).count() as u64;
+        Ok(Some((better, decode_entry(&val)?)))
+    }
+
+    fn remove_score(&self, board: &str, name: &str) -> Result<bool, Error> {
+        let (scores, best) = self.board(board)?;
+        let res: Result<bool, TransactionError> = (&scores, &best).transaction(|(scores, best)| {
+            let Some(key) = best.remove(name.as_bytes())? else {
+                return Ok(false);
+            };
+            scores.remove(key)?;
+            Ok(true)
+        });
+        match res {
+            Ok(removed) => Ok(removed),
+            Err(TransactionError::Storage(e)) => Err(storage_error(e)),
+            // nothing in the transaction aborts
+            Err(TransactionError::Abort(())) => unreachable!(),
+        }
+    }
+
+    fn boards(&self) -> Result<Vec<String>, Error> {
+        Ok(self
+            .db
+            .tree_names()
+            .iter()
+            .filter_map(|name| name.strip_prefix(b"scores-"))
+            .map(|board| String::from_utf8_lossy(board).into_owned())
+            .collect())
+    }
+
+    fn remove_board(&self, board: &str) -> Result<(), Error> {
+        for tree in [format!("scores-{board}"), format!("best-{board}")] {
+            self.db.drop_tree(tree).map_err(storage_error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_code_is_an_error() {
+        let storage = SledStorage::temporary();
+        storage.codes.insert("K7Q-4MX", &[1, 2, 3][..]).unwrap();
+        assert!(matches!(
+            storage.game_by_code("K7Q-4MX"),
+            Err(Error::Storage)
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use bevy_math::Vec2;
+use rand::{seq::SliceRandom, Rng};
+
+/// The number of seconds between bombs on the first wave.
+pub const BASE_BOMB_INTERVAL: f32 = 1.2;
+/// The bomb interval can't be made shorter than this, however many waves the
+/// players survive.
+pub const MIN_BOMB_INTERVAL: f32 = 0.3;
+/// How much the bomb interval is multiplied by on every wave after the first.
+pub const BOMB_INTERVAL_WAVE_FACTOR: f32 = 0.85;
+/// The most bombs that can be falling at once.
+pub const MAX_BOMBS: usize = 3;
+/// How far below the alien that drops it a bomb appears.
+const DROP_OFFSET_Y: f32 = 16.0;
+
+/// Returns the number of seconds between bombs on the given wave. The aliens
+/// drop bombs more often on every wave.
+pub fn bomb_interval(wave: u32) -> f32 {
+    let interval =
+        BASE_BOMB_INTERVAL * BOMB_INTERVAL_WAVE_FACTOR.powi(wave.saturating_sub(1) as i32);
+    interval.max(MIN_BOMB_INTERVAL)
+}
+
+/// Which column a bomb is dropped from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shot {
+    /// The column closest to a ship.
+    Aimed,
+    /// A random column.
+    Random,
+}
+
+/// Decides when the aliens drop bombs. Like the arcade original, aimed shots
+/// alternate with random ones, starting with a random shot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BombClock {
+    interval: f32,
+    /// Seconds until the next bomb is due.
+    until_next: f32,
+    aim_next: bool,
+}
+
+impl Default for BombClock {
+    fn default() -> Self {
+        Self::for_wave(1)
+    }
+}
+
+impl BombClock {
+    pub fn for_wave(wave: u32) -> Self {
+        let interval = bomb_interval(wave);
+        Self {
+            interval,
+            until_next: interval,
+            aim_next: false,
+        }
+    }
+
+    /// Advances the clock by `dt` seconds, returning the shot to take if a
+    /// bomb is due. `falling` is how many bombs are already on screen; no bomb
+    /// is dropped while there are [`MAX_BOMBS`] of them.
+    pub fn tick(&mut self, dt: f32, falling: usize) -> Option<Shot> {
+        self.until_next -= dt;
+        if self.until_next > 0.0 {
+            return None;
+        }
+        self.until_next += self.interval;
+        if falling >= MAX_BOMBS {
+            return None;
+        }
+        let aimed = self.aim_next;
+        self.aim_next = !aimed;
+        Some(if aimed { Shot::Aimed } else { Shot::Random })
+    }
+}
+
+/// Returns the bottom alien of each column of the formation, from left to
+/// right. They're the only aliens with a clear line of fire. Each alien's
+/// position comes paired with whatever else the caller needs to know about it.
+pub fn bottom_of_columns<T>(aliens: impl IntoIterator<Item = (Vec2, T)>) -> Vec<(Vec2, T)> {
+    // every alien in a column moves by exactly the same amount, so they all
+    // share the same x position
+    let mut columns: HashMap<i32, (Vec2, T)> = HashMap::new();
+    for (pos, alien) in aliens {
+        let column = pos.x.round() as i32;
+        match columns.get(&column) {
+            Some((bottom, _)) if bottom.y <= pos.y => {}
+            _ => {
+                columns.insert(column, (pos, alien));
+            }
+        }
+    }
+    // sorted so the same seed always picks the same columns
+    let mut bottoms: Vec<(Vec2, T)> = columns.into_values().collect();
+    bottoms.sort_by(|a, b| a.0.x.total_cmp(&b.0.x));
+    bottoms
+}
+
+/// Picks which of `shooters` takes a shot. Aimed shots come from the shooter
+/// closest to `target_x`, and aren't taken at all without a target.
+pub fn choose_shooter(
+    shot: Shot,
+    shooters: &[Vec2],
+    target_x: Option<f32>,
+    rng: &mut impl Rng,
+) -> Option<Vec2> {
+    match shot {
+        Shot::Aimed => {
+            let target_x = target_x?;
+            shooters
+                .iter()
+                .min_by(|a, b| (a.x - target_x).abs().total_cmp(&(b.x - target_x).abs()))
+                .copied()
+        }
+        Shot::Random => shooters.choose(rng).copied(),
+    }
+}
+
+/// Returns where a bomb dropped by the alien at `shooter` appears.
+pub fn drop_position(shooter: Vec2) -> Vec2 {
+    Vec2::new(shooter.x, shooter.y - DROP_OFFSET_Y)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn bombs_come_faster_every_wave_down_to_a_minimum() {
+        assert_eq!(bomb_interval(1), BASE_BOMB_INTERVAL);
+        assert!(bomb_interval(2) < bomb_interval(1));
+        assert_eq!(bomb_interval(100), MIN_BOMB_INTERVAL);
+    }
+
+    #[test]
+    fn shots_alternate_and_wait_for_bombs_to_clear() {
+        let mut clock = BombClock::for_wave(1);
+        assert_eq!(clock.tick(BASE_BOMB_INTERVAL / 2.0, 0), None);
+        assert_eq!(clock.tick(BASE_BOMB_INTERVAL / 2.0, 0), Some(Shot::Random));
+        assert_eq!(clock.tick(BASE_BOMB_INTERVAL, MAX_BOMBS), None);
+        assert_eq!(clock.tick(BASE_BOMB_INTERVAL, 0), Some(Shot::Aimed));
+        assert_eq!(clock.tick(BASE_BOMB_INTERVAL, 0), Some(Shot::Random));
+    }
+
+    #[test]
+    fn only_the_bottom_alien_of_each_column_shoots() {
+        let aliens = [
+            (Vec2::new(32.0, 0.0), 'a'),
+            (Vec2::new(0.0, 0.0), 'b'),
+            (Vec2::new(0.0, -32.0), 'c'),
+            (Vec2::new(32.2, 32.0), 'd'),
+        ];
+        let bottoms: Vec<char> = bottom_of_columns(aliens)
+            .into_iter()
+            .map(|(_, alien)| alien)
+            .collect();
+        assert_eq!(bottoms, ['c', 'a']);
+    }
+
+    #[test]
+    fn aimed_shots_come_from_the_closest_column() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let shooters = [Vec2::new(-100.0, 0.0), Vec2::new(50.0, 0.0)];
+        assert_eq!(
+            choose_shooter(Shot::Aimed, &shooters, Some(20.0), &mut rng),
+            Some(shooters[1])
+        );
+        assert_eq!(choose_shooter(Shot::Aimed, &shooters, None, &mut rng), None);
+        assert!(choose_shooter(Shot::Random, &shooters, None, &mut rng).is_some());
+    }
+}
@@ -0,0 +1,171 @@
+use bevy_math::{Rect, Vec2};
+
+// generated from the sprite sheet by the game's build script
+const COLLISION_MATRICES: &[u8; 2048] =
+    include_bytes!("../../game/assets/sprite_collision_matrices.bin");
+pub const SPRITE_W: usize = 32;
+pub const SPRITE_H: usize = 32;
+const SPRITE_COLS: usize = 8;
+const SPRITE_ROWS: usize = 2;
+pub const SPRITE_N: usize = SPRITE_COLS * SPRITE_ROWS;
+const BITS_PER_MATRIX: usize = SPRITE_W * SPRITE_H;
+const SPRITE_HALF_W: f32 = SPRITE_W as f32 / 2.0;
+const SPRITE_HALF_H: f32 = SPRITE_H as f32 / 2.0;
+
+// A 2D array representing the collision matrix of a sprite,
+// where the first dimension is the y-coordinate (rows, counted from the bottom
+// of the sprite) and the second dimension is the x-coordinate (columns).
+pub type CollisionMatrix = [[bool; SPRITE_W]; SPRITE_H];
+
+#[derive(Default)]
+pub struct CollisionMatrices([CollisionMatrix; SPRITE_N]);
+
+pub fn load_collision_matrices() -> CollisionMatrices {
+    let mut collision_matrices = [CollisionMatrix::default(); SPRITE_N];
+
+    for (matrix_index, matrix) in collision_matrices.iter_mut().enumerate() {
+        for bit_index in 0..BITS_PER_MATRIX {
+            // calculate which byte this bit is in and whether it's set
+            let byte_index = (matrix_index * BITS_PER_MATRIX + bit_index) / 8;
+            let bit_position = bit_index % 8;
+            let byte = COLLISION_MATRICES[byte_index];
+            let bit_set = (byte & (1 << bit_position)) != 0;
+
+            // The build script packs each matrix column by column (x-major, with y
+            // counting down from the top of the image). Rows are stored bottom-up so
+            // that they line up with world space, where y increases upwards.
+            let column = bit_index / SPRITE_H;
+            let row = SPRITE_H - 1 - bit_index % SPRITE_H;
+            matrix[row][column] = bit_set;
+        }
+    }
+
+    CollisionMatrices(collision_matrices)
+}
+
+/// Performs a collision check between two sprites - a and b. Returns true if
+/// they collide. Uses pre-calculated collision matrices to perform the check.
+pub fn collide(matrices: &CollisionMatrices, a: usize, b: usize, a_pos: Vec2, b_pos: Vec2) -> bool {
+    let a_matrix = &matrices.0[a];
+    let b_matrix = &matrices.0[b];
+
+    // Calculate the bounding box for both sprites based on the center position.
+    let a_min = Vec2::new(a_pos.x - SPRITE_HALF_W, a_pos.y - SPRITE_HALF_H);
+    let a_max = Vec2::new(a_pos.x + SPRITE_HALF_W, a_pos.y + SPRITE_HALF_H);
+    let b_min = Vec2::new(b_pos.x - SPRITE_HALF_W, b_pos.y - SPRITE_HALF_H);
+    let b_max = Vec2::new(b_pos.x + SPRITE_HALF_W, b_pos.y + SPRITE_HALF_H);
+
+    // Check if the bounding boxes overlap; if not, there's no collision.
+    if a_max.x <= b_min.x || a_min.x >= b_max.x || a_max.y <= b_min.y || a_min.y >= b_max.y {
+        return false;
+    }
+
+    // Calculate the overlapping rectangle (intersecting area).
+    let overlap_x_start = a_min.x.max(b_min.x).ceil() as isize;
+    let overlap_y_start = a_min.y.max(b_min.y).ceil() as isize;
+    let overlap_x_end = a_max.x.min(b_max.x).floor() as isize;
+    let overlap_y_end = a_max.y.min(b_max.y).floor() as isize;
+
+    // Calculate the local sprite matrix coordinates for the top-left corner of the
+    // overlapping area.
+    let a_local_min_x = (overlap_x_start - (a_pos.x - SPRITE_HALF_W) as isize).max(0) as usize;
+    let a_local_min_y = (overlap_y_start - (a_pos.y - SPRITE_HALF_H) as isize).max(0) as usize;
+    let b_local_min_x = (overlap_x_start - (b_pos.x - SPRITE_HALF_W) as isize).max(0) as usize;
+    let b_local_min_y = (overlap_y_start - (b_pos.y - SPRITE_HALF_H) as isize).max(0) as usize;
+
+    // Determine the width and height of the overlapping area.
+    let overlap_width = (overlap_x_end - overlap_x_start).max(0) as usize;
+    let overlap_height = (overlap_y_end - overlap_y_start).max(0) as usize;
+
+    // Iterate over the width and height of the overlapping area
+    // by traversing the local coordinate space of each sprite's collision matrix.
+    for y in 0..overlap_height {
+        let a_matrix_y = a_local_min_y + y;
+        for x in 0..overlap_width {
+            let a_matrix_x = a_local_min_x + x;
+            let b_matrix_x = b_local_min_x + x;
+            let b_matrix_y = b_local_min_y + y;
+
+            // Perform collision check and return true if a collision is detected.
+            if a_matrix[a_matrix_y][a_matrix_x] && b_matrix[b_matrix_y][b_matrix_x] {
+                return true;
+            }
+        }
+    }
+
+    // No collision found if the loop completes without returning true.
+    false
+}
+
+/// Performs a collision check between a sprite and an axis-aligned rectangle in
+/// world space. Returns true if any opaque pixel of the sprite lies inside the
+/// rectangle. Used for things that aren't sprites in the atlas, such as the
+/// individual pixels of a shield.
+pub fn collide_rect(matrices: &CollisionMatrices, a: usize, a_pos: Vec2, rect: Rect) -> bool {
+    let a_matrix = &matrices.0[a];
+
+    let a_rect = sprite_bounds(a_pos);
+    let overlap = a_rect.intersect(rect);
+    if overlap.is_empty() {
+        return false;
+    }
+
+    // Convert the overlapping area into the local coordinate space of the sprite's
+    // collision matrix, clamping to the matrix bounds.
+    let local_min = (overlap.min - a_rect.min).floor();
+    let local_max = (overlap.max - a_rect.min).ceil();
+    let x_start = (local_min.x.max(0.) as usize).min(SPRITE_W);
+    let y_start = (local_min.y.max(0.) as usize).min(SPRITE_H);
+    let x_end = (local_max.x.max(0.) as usize).min(SPRITE_W);
+    let y_end = (local_max.y.max(0.) as usize).min(SPRITE_H);
+
+    a_matrix[y_start..y_end]
+        .iter()
+        .any(|row| row[x_start..x_end].iter().any(|&pixel| pixel))
+}
+
+/// Returns the bounding box of a sprite centred at `pos`.
+pub fn sprite_bounds(pos: Vec2) -> Rect {
+    Rect::from_center_size(pos, Vec2::new(SPRITE_W as f32, SPRITE_H as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIP: usize = 13;
+    const LASER: usize = 10;
+
+    #[test]
+    fn sprites_collide_with_themselves_but_not_far_away() {
+        let matrices = load_collision_matrices();
+        assert!(collide(&matrices, SHIP, SHIP, Vec2::ZERO, Vec2::ZERO));
+        assert!(!collide(
+            &matrices,
+            SHIP,
+            SHIP,
+            Vec2::ZERO,
+            Vec2::new(SPRITE_W as f32, 0.0)
+        ));
+    }
+
+    #[test]
+    fn lasers_only_hit_where_they_have_pixels() {
+        let matrices = load_collision_matrices();
+        // the laser is a thin line down the middle of its sprite
+        let edge = Rect::new(-16.0, -16.0, -12.0, 16.0);
+        assert!(!collide_rect(&matrices, LASER, Vec2::ZERO, edge));
+        assert!(collide_rect(
+            &matrices,
+            LASER,
+            Vec2::ZERO,
+            sprite_bounds(Vec2::ZERO)
+        ));
+        assert!(!collide_rect(
+            &matrices,
+            LASER,
+            Vec2::ZERO,
+            Rect::new(100.0, 100.0, 110.0, 110.0)
+        ));
+    }
+}
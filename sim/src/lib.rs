@@ -0,0 +1,10 @@
+// The rules of Cosmos Raiders, with no dependency on Bevy's ECS. The game runs
+// these rules from its Bevy systems, and the server runs them in its own tick
+// loop to keep an authoritative copy of every online game.
+
+pub mod bombs;
+pub mod collisions;
+pub mod march;
+pub mod simulation;
+
+pub use bevy_math::{Rect, Vec2};
@@ -0,0 +1,108 @@
+use bevy_math::Vec2;
+
+/// How far either side of the centre of the screen the formation marches
+/// before it steps down and turns around.
+pub const SCREEN_BOUNDARY_X: f32 = 300.0;
+/// How many logical pixels to move the aliens down by when they hit the screen
+/// boundary.
+const DOWN_STEP_Y: f32 = 24.0;
+/// How many times faster than the full formation the last alien standing
+/// moves.
+const MAX_SPEEDUP: f32 = 6.0;
+/// How many logical pixels the formation marches between each step of the
+/// march. At the base velocity this is one step every half second.
+pub const STEP_DISTANCE: f32 = 50.0;
+
+/// Where the formation is in its march.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum AlienMovement {
+    Left,
+    #[default]
+    Right,
+    Down {
+        pixels_left_to_move: f32,
+        should_move_left_after: bool,
+    },
+}
+
+impl AlienMovement {
+    /// Marches the formation `distance` pixels, returning how far every alien
+    /// should move. `xs` are the x positions of the aliens before they move.
+    pub fn step(&mut self, xs: impl IntoIterator<Item = f32>, distance: f32) -> Vec2 {
+        let (min_x, max_x) = xs
+            .into_iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+                (min.min(x), max.max(x))
+            });
+        match *self {
+            AlienMovement::Left => {
+                if min_x - distance <= -SCREEN_BOUNDARY_X {
+                    *self = AlienMovement::Down {
+                        pixels_left_to_move: DOWN_STEP_Y,
+                        should_move_left_after: false,
+                    };
+                }
+                Vec2::new(-distance, 0.0)
+            }
+            AlienMovement::Right => {
+                if max_x + distance >= SCREEN_BOUNDARY_X {
+                    *self = AlienMovement::Down {
+                        pixels_left_to_move: DOWN_STEP_Y,
+                        should_move_left_after: true,
+                    };
+                }
+                Vec2::new(distance, 0.0)
+            }
+            AlienMovement::Down {
+                ref mut pixels_left_to_move,
+                should_move_left_after,
+            } => {
+                let move_down = f32::min(*pixels_left_to_move, distance);
+                *pixels_left_to_move -= distance;
+
+                // If the aliens have finished moving down, change horizontal direction
+                if *pixels_left_to_move <= 0.0 {
+                    *self = if should_move_left_after {
+                        AlienMovement::Left
+                    } else {
+                        AlienMovement::Right
+                    };
+                }
+                Vec2::new(0.0, -move_down)
+            }
+        }
+    }
+}
+
+/// Returns the velocity of a formation that started with `size` aliens and has
+/// `remaining` left. Like the arcade original, the formation speeds up as
+/// aliens die, slowly at first and much faster towards the end.
+/// `full_velocity` is the velocity of the full formation.
+pub fn formation_velocity(remaining: usize, size: usize, full_velocity: f32) -> f32 {
+    // splitting aliens can leave more aliens than the formation started with
+    let killed_fraction = (1.0 - remaining as f32 / size.max(1) as f32).clamp(0.0, 1.0);
+    full_velocity * (1.0 + (MAX_SPEEDUP - 1.0) * killed_fraction.powi(2))
+}
+
+/// Counts the formation's steps as it marches. Every step plays a note of the
+/// heartbeat and moves the aliens on to their next sprite.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct MarchSteps {
+    /// How far the formation has marched since its last step.
+    distance: f32,
+}
+
+impl MarchSteps {
+    /// Marches the formation `distance` pixels, returning whether it took a
+    /// step.
+    pub fn advance(&mut self, distance: f32) -> bool {
+        self.distance += distance;
+        if self.distance < STEP_DISTANCE {
+            return false;
+        }
+        // a very fast formation could cover more than one step at once, but
+        // there's no point taking more than one at a time
+        self.distance %= STEP_DISTANCE;
+        true
+    }
+}
@@ -0,0 +1,607 @@
+use bevy_math::Vec2;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    bombs::{bottom_of_columns, choose_shooter, drop_position, BombClock, Shot},
+    collisions::{collide, load_collision_matrices, CollisionMatrices},
+    march::{formation_velocity, AlienMovement, MarchSteps, SCREEN_BOUNDARY_X},
+};
+
+/// How fast a laser travels up the screen, in pixels per second.
+pub const LASER_VELOCITY: f32 = 480.0;
+pub const LASER_SPRITE_INDEX: usize = 10;
+/// How fast a bomb falls down the screen, in pixels per second.
+pub const BOMB_VELOCITY: f32 = 200.0;
+pub const BOMB_SPRITE_INDEX: usize = 9;
+pub const SHIP_SPRITE_INDEX: usize = 13;
+/// The height of the player's ships.
+pub const SHIP_Y: f32 = -130.0;
+/// The number of ships each player starts the game with.
+pub const STARTING_LIVES: u32 = 3;
+/// How far a ship can move either side of the centre of the screen.
+pub const SHIP_BOUNDARY_X: f32 = 350.0;
+/// Lasers are removed once they're this far up the screen.
+const LASER_MAX_Y: f32 = 350.0;
+/// Bombs are removed once they're this far down the screen.
+const BOMB_MIN_Y: f32 = -350.0;
+/// How long a destroyed ship takes to respawn, in seconds.
+const RESPAWN_DELAY_SECS: f32 = 2.5;
+/// How long a respawned ship can't be hit for, in seconds.
+const INVULNERABLE_SECS: f32 = 2.0;
+/// The velocity of the full classic formation, in pixels per second.
+const CLASSIC_VELOCITY: f32 = 100.0;
+const CLASSIC_START_Y: f32 = 200.0;
+const CLASSIC_SPACING: f32 = 32.0;
+const CLASSIC_COLUMNS: usize = 11;
+/// The sprite and points of each row of the classic formation, top row first.
+const CLASSIC_ROWS: [(usize, u32); 5] = [(0, 30), (2, 20), (2, 20), (4, 10), (4, 10)];
+
+#[derive(Debug, Clone)]
+pub struct SimAlien {
+    pub pos: Vec2,
+    pub points: u32,
+    /// The sprite showing on this step of the march. Every alien kind has a
+    /// pair of sprites that it alternates between.
+    pub sprite_index: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimLaser {
+    pub pos: Vec2,
+    /// The name of the player whose ship fired the laser.
+    pub owner: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimBomb {
+    pub pos: Vec2,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimShip {
+    pub name: String,
+    pub x: f32,
+    pub score: u32,
+    /// How many ships the player has left, including the one in play.
+    pub lives: u32,
+    /// Seconds until a destroyed ship respawns, or 0 while it's in play.
+    pub respawn_in: f32,
+    /// Seconds until a respawned ship can be hit again.
+    pub invulnerable_for: f32,
+}
+
+impl SimShip {
+    /// Whether the ship is on screen, so it can fire and be hit.
+    pub fn in_play(&self) -> bool {
+        self.lives > 0 && self.respawn_in <= 0.0
+    }
+}
+
+/// Something that happened during a tick, for the server to pass on to
+/// clients.
+#[derive(Debug, Clone)]
+pub enum SimEvent {
+    AlienKilled {
+        by: String,
+        pos: Vec2,
+        points: u32,
+    },
+    /// A player's score went up, to `score`.
+    ScoreChanged {
+        name: String,
+        score: u32,
+    },
+    /// A new formation appeared.
+    WaveStarted {
+        wave: u32,
+    },
+    WaveCleared {
+        wave: u32,
+    },
+    /// A player's ship was hit by a bomb, or the aliens reached it. `lives`
+    /// is how many ships they have left.
+    ShipDestroyed {
+        name: String,
+        lives: u32,
+    },
+    /// The aliens reached the ships, or every player ran out of lives.
+    GameOver,
+}
+
+/// Whether a laser at `laser_pos` hits an alien showing `alien_sprite` at
+/// `alien_pos`.
+pub fn laser_hits_alien(
+    matrices: &CollisionMatrices,
+    laser_pos: Vec2,
+    alien_sprite: usize,
+    alien_pos: Vec2,
+) -> bool {
+    collide(
+        matrices,
+        LASER_SPRITE_INDEX,
+        alien_sprite,
+        laser_pos,
+        alien_pos,
+    )
+}
+
+/// Whether a bomb showing `bomb_sprite` at `bomb_pos` hits a ship at
+/// `ship_pos`.
+pub fn bomb_hits_ship(
+    matrices: &CollisionMatrices,
+    bomb_sprite: usize,
+    bomb_pos: Vec2,
+    ship_pos: Vec2,
+) -> bool {
+    collide(matrices, bomb_sprite, SHIP_SPRITE_INDEX, bomb_pos, ship_pos)
+}
+
+/// A complete online game: the formation and its bombs, and every player's
+/// ship and lasers.
+///
+/// Online games always use the classic formation, without the UFO or the
+/// shields, so scores can be compared between them.
+pub struct Simulation {
+    pub tick: u64,
+    pub wave: u32,
+    pub aliens: Vec<SimAlien>,
+    pub lasers: Vec<SimLaser>,
+    pub bombs: Vec<SimBomb>,
+    pub ships: Vec<SimShip>,
+    pub game_over: bool,
+    movement: AlienMovement,
+    formation_size: usize,
+    /// Changes the aliens' sprites on every step of the march.
+    march_steps: MarchSteps,
+    bomb_clock: BombClock,
+    matrices: CollisionMatrices,
+    rng: StdRng,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+}
+
+impl Simulation {
+    /// Creates a game whose bombs fall the same way every time for the same
+    /// seed.
+    pub fn seeded(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(rng: StdRng) -> Self {
+        let mut sim = Self {
+            tick: 0,
+            wave: 1,
+            aliens: Vec::new(),
+            lasers: Vec::new(),
+            bombs: Vec::new(),
+            ships: Vec::new(),
+            game_over: false,
+            movement: AlienMovement::default(),
+            formation_size: 0,
+            march_steps: MarchSteps::default(),
+            bomb_clock: BombClock::default(),
+            matrices: load_collision_matrices(),
+            rng,
+        };
+        sim.spawn_formation();
+        sim
+    }
+
+    /// Spawns the classic 5 by 11 formation, starting at the left edge of the
+    /// screen.
+    fn spawn_formation(&mut self) {
+        self.aliens.clear();
+        for (row, &(sprite_index, points)) in CLASSIC_ROWS.iter().enumerate() {
+            for col in 0..CLASSIC_COLUMNS {
+                self.aliens.push(SimAlien {
+                    pos: Vec2::new(
+                        -SCREEN_BOUNDARY_X + col as f32 * CLASSIC_SPACING,
+                        CLASSIC_START_Y - row as f32 * CLASSIC_SPACING,
+                    ),
+                    points,
+                    sprite_index,
+                });
+            }
+        }
+        self.formation_size = self.aliens.len();
+        self.movement = AlienMovement::default();
+        self.march_steps = MarchSteps::default();
+        self.bombs.clear();
+        self.bomb_clock = BombClock::for_wave(self.wave);
+    }
+
+    pub fn add_ship(&mut self, name: String) {
+        if self.ships.iter().any(|ship| ship.name == name) {
+            return;
+        }
+        self.ships.push(SimShip {
+            name,
+            x: 0.0,
+            score: 0,
+            lives: STARTING_LIVES,
+            respawn_in: 0.0,
+            invulnerable_for: 0.0,
+        });
+    }
+
+    /// Removes a player's ship, and any laser they have in flight.
+    pub fn remove_ship(&mut self, name: &str) {
+        self.ships.retain(|ship| ship.name != name);
+        self.lasers.retain(|laser| laser.owner != name);
+    }
+
+    pub fn set_ship_x(&mut self, name: &str, x: f32) {
+        if let Some(ship) = self.ships.iter_mut().find(|ship| ship.name == name) {
+            ship.x = x.clamp(-SHIP_BOUNDARY_X, SHIP_BOUNDARY_X);
+        }
+    }
+
+    /// Fires a laser from a player's ship. Like the arcade original, each
+    /// player can only have one laser on screen at a time, and destroyed ships
+    /// can't fire. Returns whether the laser was fired.
+    pub fn fire(&mut self, name: &str) -> bool {
+        let Some(ship) = self.ships.iter().find(|ship| ship.name == name) else {
+            return false;
+        };
+        if !ship.in_play() {
+            return false;
+        }
+        if self.game_over || self.lasers.iter().any(|laser| laser.owner == name) {
+            return false;
+        }
+        self.lasers.push(SimLaser {
+            pos: Vec2::new(ship.x, SHIP_Y + 16.0),
+            owner: ship.name.clone(),
+        });
+        true
+    }
+
+    /// Advances the game by `dt` seconds.
+    pub fn step(&mut self, dt: f32) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        self.tick += 1;
+        if self.game_over {
+            return events;
+        }
+        if self.tick == 1 {
+            events.push(SimEvent::WaveStarted { wave: self.wave });
+        }
+
+        self.march(dt);
+        for laser in &mut self.lasers {
+            laser.pos.y += LASER_VELOCITY * dt;
+        }
+        self.lasers.retain(|laser| laser.pos.y <= LASER_MAX_Y);
+        self.collide_lasers(&mut events);
+        self.respawn_ships(dt);
+        self.drop_bombs(dt);
+        for bomb in &mut self.bombs {
+            bomb.pos.y -= BOMB_VELOCITY * dt;
+        }
+        self.bombs.retain(|bomb| bomb.pos.y >= BOMB_MIN_Y);
+        self.collide_bombs(&mut events);
+
+        if self.aliens.is_empty() {
+            events.push(SimEvent::WaveCleared { wave: self.wave });
+            self.wave += 1;
+            self.spawn_formation();
+            events.push(SimEvent::WaveStarted { wave: self.wave });
+        } else if self.aliens.iter().any(|alien| alien.pos.y < SHIP_Y) {
+            // the invasion takes every ship left with it
+            for ship in self.ships.iter_mut().filter(|ship| ship.lives > 0) {
+                ship.lives = 0;
+                events.push(SimEvent::ShipDestroyed {
+                    name: ship.name.clone(),
+                    lives: 0,
+                });
+            }
+        }
+
+        if !self.ships.is_empty() && self.ships.iter().all(|ship| ship.lives == 0) {
+            self.game_over = true;
+            events.push(SimEvent::GameOver);
+        }
+        events
+    }
+
+    /// Brings destroyed ships with lives left back to the centre of the
+    /// screen, where they can't be hit for a while.
+    fn respawn_ships(&mut self, dt: f32) {
+        for ship in self.ships.iter_mut().filter(|ship| ship.lives > 0) {
+            ship.invulnerable_for = (ship.invulnerable_for - dt).max(0.0);
+            if ship.respawn_in <= 0.0 {
+                continue;
+            }
+            ship.respawn_in -= dt;
+            if ship.respawn_in <= 0.0 {
+                ship.respawn_in = 0.0;
+                ship.x = 0.0;
+                ship.invulnerable_for = INVULNERABLE_SECS;
+            }
+        }
+    }
+
+    /// Drops a bomb from the bottom alien of a column whenever the bomb clock
+    /// says so. Like the game, bombs alternate between the column closest to
+    /// a ship and a random column.
+    fn drop_bombs(&mut self, dt: f32) {
+        let Some(shot) = self.bomb_clock.tick(dt, self.bombs.len()) else {
+            return;
+        };
+        let shooters: Vec<Vec2> =
+            bottom_of_columns(self.aliens.iter().map(|alien| (alien.pos, ())))
+                .into_iter()
+                .map(|(pos, _)| pos)
+                .collect();
+        let target_x = if shot == Shot::Aimed {
+            let targets: Vec<f32> = self
+                .ships
+                .iter()
+                .filter(|ship| ship.in_play())
+                .map(|ship| ship.x)
+                .collect();
+            targets.choose(&mut self.rng).copied()
+        } else {
+            None
+        };
+        if let Some(shooter) = choose_shooter(shot, &shooters, target_x, &mut self.rng) {
+            self.bombs.push(SimBomb {
+                pos: drop_position(shooter),
+            });
+        }
+    }
+
+    fn collide_bombs(&mut self, events: &mut Vec<SimEvent>) {
+        for ship in self.ships.iter_mut() {
+            if !ship.in_play() || ship.invulnerable_for > 0.0 {
+                continue;
+            }
+            let ship_pos = Vec2::new(ship.x, SHIP_Y);
+            let hit = self.bombs.iter().position(|bomb| {
+                bomb_hits_ship(&self.matrices, BOMB_SPRITE_INDEX, bomb.pos, ship_pos)
+            });
+            let Some(bomb_index) = hit else {
+                continue;
+            };
+            self.bombs.swap_remove(bomb_index);
+            ship.lives -= 1;
+            ship.respawn_in = RESPAWN_DELAY_SECS;
+            events.push(SimEvent::ShipDestroyed {
+                name: ship.name.clone(),
+                lives: ship.lives,
+            });
+        }
+    }
+
+    fn march(&mut self, dt: f32) {
+        if self.aliens.is_empty() {
+            return;
+        }
+        let velocity = formation_velocity(self.aliens.len(), self.formation_size, CLASSIC_VELOCITY);
+        let distance = velocity * dt;
+        let delta = self
+            .movement
+            .step(self.aliens.iter().map(|alien| alien.pos.x), distance);
+        let change_sprite = self.march_steps.advance(distance);
+        for alien in &mut self.aliens {
+            alien.pos += delta;
+            if change_sprite {
+                alien.sprite_index ^= 1;
+            }
+        }
+    }
+
+    fn collide_lasers(&mut self, events: &mut Vec<SimEvent>) {
+        let mut i = 0;
+        while i < self.lasers.len() {
+            let laser = &self.lasers[i];
+            let hit = self.aliens.iter().position(|alien| {
+                laser_hits_alien(&self.matrices, laser.pos, alien.sprite_index, alien.pos)
+            });
+            let Some(alien_index) = hit else {
+                i += 1;
+                continue;
+            };
+
+            let laser = self.lasers.swap_remove(i);
+            let alien = self.aliens.swap_remove(alien_index);
+            let ship = self.ships.iter_mut().find(|ship| ship.name == laser.owner);
+            let score_changed = ship.map(|ship| {
+                ship.score += alien.points;
+                SimEvent::ScoreChanged {
+                    name: ship.name.clone(),
+                    score: ship.score,
+                }
+            });
+            events.push(SimEvent::AlienKilled {
+                by: laser.owner,
+                pos: alien.pos,
+                points: alien.points,
+            });
+            events.extend(score_changed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bombs::BASE_BOMB_INTERVAL;
+
+    const DT: f32 = 1.0 / 30.0;
+
+    fn sim_with_ships(names: &[&str]) -> Simulation {
+        let mut sim = Simulation::seeded(1);
+        for name in names {
+            sim.add_ship(name.to_string());
+        }
+        sim
+    }
+
+    fn ship<'a>(sim: &'a Simulation, name: &str) -> &'a SimShip {
+        sim.ships.iter().find(|ship| ship.name == name).unwrap()
+    }
+
+    #[test]
+    fn ships_are_added_once_and_clamped_to_the_screen() {
+        let mut sim = sim_with_ships(&["alice", "alice"]);
+        assert_eq!(sim.ships.len(), 1);
+
+        sim.set_ship_x("alice", 10_000.0);
+        assert_eq!(ship(&sim, "alice").x, SHIP_BOUNDARY_X);
+    }
+
+    #[test]
+    fn each_player_has_one_laser_at_a_time() {
+        let mut sim = sim_with_ships(&["alice", "bob"]);
+        assert!(sim.fire("alice"));
+        assert!(!sim.fire("alice"));
+        assert!(sim.fire("bob"));
+        assert!(!sim.fire("carol"));
+
+        sim.remove_ship("alice");
+        assert!(sim.lasers.iter().all(|laser| laser.owner == "bob"));
+    }
+
+    #[test]
+    fn the_first_step_starts_the_first_wave() {
+        let mut sim = sim_with_ships(&["alice"]);
+        let events = sim.step(DT);
+        assert!(matches!(events[0], SimEvent::WaveStarted { wave: 1 }));
+        assert!(!sim
+            .step(DT)
+            .iter()
+            .any(|ev| matches!(ev, SimEvent::WaveStarted { .. })));
+    }
+
+    #[test]
+    fn lasers_kill_aliens_and_score_for_their_owner() {
+        let mut sim = sim_with_ships(&["alice"]);
+        let target = sim.aliens[0].clone();
+        sim.lasers.push(SimLaser {
+            pos: target.pos,
+            owner: "alice".to_string(),
+        });
+
+        let events = sim.step(0.0);
+        assert!(sim.lasers.is_empty());
+        assert_eq!(sim.aliens.len(), sim.formation_size - 1);
+        assert_eq!(ship(&sim, "alice").score, target.points);
+        assert!(events.iter().any(|ev| matches!(
+            ev,
+            SimEvent::AlienKilled { by, points, .. } if by == "alice" && *points == target.points
+        )));
+        assert!(events.iter().any(|ev| matches!(
+            ev,
+            SimEvent::ScoreChanged { name, score } if name == "alice" && *score == target.points
+        )));
+    }
+
+    #[test]
+    fn clearing_the_formation_starts_the_next_wave() {
+        let mut sim = sim_with_ships(&["alice"]);
+        sim.step(DT);
+        sim.aliens.clear();
+
+        let events = sim.step(DT);
+        assert!(matches!(events[0], SimEvent::WaveCleared { wave: 1 }));
+        assert!(matches!(events[1], SimEvent::WaveStarted { wave: 2 }));
+        assert_eq!(sim.wave, 2);
+        assert_eq!(sim.aliens.len(), sim.formation_size);
+    }
+
+    #[test]
+    fn bombs_fall_from_the_bottom_alien_of_a_column() {
+        let mut sim = sim_with_ships(&["alice"]);
+        let lowest_y = sim
+            .aliens
+            .iter()
+            .map(|alien| alien.pos.y)
+            .fold(f32::INFINITY, f32::min);
+        sim.step(BASE_BOMB_INTERVAL);
+
+        assert_eq!(sim.bombs.len(), 1);
+        assert!(sim.bombs[0].pos.y < lowest_y);
+    }
+
+    #[test]
+    fn bombs_fall_the_same_way_for_the_same_seed() {
+        let mut a = sim_with_ships(&["alice"]);
+        let mut b = sim_with_ships(&["alice"]);
+        for _ in 0..300 {
+            a.step(DT);
+            b.step(DT);
+        }
+        let positions =
+            |sim: &Simulation| -> Vec<Vec2> { sim.bombs.iter().map(|bomb| bomb.pos).collect() };
+        assert_eq!(positions(&a), positions(&b));
+    }
+
+    #[test]
+    fn bombs_destroy_ships_which_respawn_invulnerable() {
+        let mut sim = sim_with_ships(&["alice"]);
+        sim.set_ship_x("alice", 100.0);
+        sim.bombs.push(SimBomb {
+            pos: Vec2::new(100.0, SHIP_Y),
+        });
+
+        let events = sim.step(0.0);
+        assert!(events.iter().any(|ev| matches!(
+            ev,
+            SimEvent::ShipDestroyed { name, lives: 2 } if name == "alice"
+        )));
+        let alice = ship(&sim, "alice");
+        assert_eq!(alice.lives, STARTING_LIVES - 1);
+        assert!(!alice.in_play());
+        assert!(!sim.fire("alice"));
+
+        sim.step(RESPAWN_DELAY_SECS);
+        let alice = ship(&sim, "alice");
+        assert!(alice.in_play());
+        assert_eq!(alice.x, 0.0);
+        assert!(alice.invulnerable_for > 0.0);
+
+        // respawned ships can't be hit straight away
+        sim.bombs.push(SimBomb {
+            pos: Vec2::new(0.0, SHIP_Y),
+        });
+        sim.step(0.0);
+        assert_eq!(ship(&sim, "alice").lives, STARTING_LIVES - 1);
+    }
+
+    #[test]
+    fn the_game_ends_when_every_ship_is_out_of_lives() {
+        let mut sim = sim_with_ships(&["alice", "bob"]);
+        sim.ships[0].lives = 0;
+        sim.ships[1].lives = 1;
+        sim.bombs.push(SimBomb {
+            pos: Vec2::new(0.0, SHIP_Y),
+        });
+
+        let events = sim.step(0.0);
+        assert!(sim.game_over);
+        assert!(matches!(events.last(), Some(SimEvent::GameOver)));
+        assert!(sim.step(DT).is_empty());
+    }
+
+    #[test]
+    fn the_aliens_landing_destroys_every_ship() {
+        let mut sim = sim_with_ships(&["alice", "bob"]);
+        for alien in &mut sim.aliens {
+            alien.pos.y = SHIP_Y - 1.0;
+        }
+
+        let events = sim.step(0.0);
+        let destroyed = events
+            .iter()
+            .filter(|ev| matches!(ev, SimEvent::ShipDestroyed { lives: 0, .. }))
+            .count();
+        assert_eq!(destroyed, 2);
+        assert!(sim.game_over);
+        assert!(matches!(events.last(), Some(SimEvent::GameOver)));
+    }
+}